@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 
 /// Simple TUI TODO Application for daily tasks.
 #[derive(Parser, Debug)]
@@ -8,6 +8,14 @@ pub struct Args {
     #[arg(short, long)]
     pub config: Option<String>,
 
+    /// Use a high-contrast style in the TUI, without blinking text
+    #[arg(long)]
+    pub high_contrast: bool,
+
+    /// Write debug logs to `todo.log` in the tasks directory
+    #[arg(short, long)]
+    pub verbose: bool,
+
     /// Subcommands; By default, the app will run in TUI mode
     #[command(subcommand)]
     pub subcmd: Option<SubCommand>,
@@ -17,7 +25,232 @@ pub struct Args {
 #[derive(Subcommand, Debug)]
 pub enum SubCommand {
     /// Show the current status of the TODO list (short)
-    Status,
+    Status {
+        /// Also show the completion weighted by the `est:` estimates of the tasks
+        #[arg(long)]
+        weighted: bool,
+        /// Also report whether the tasks listed in `required` are all done
+        #[arg(long)]
+        strict: bool,
+    },
     /// Show the current status of the TODO list (long)
-    Details,
+    Details {
+        /// Show the output through `$PAGER` (`less` by default)
+        #[arg(long)]
+        page: bool,
+        /// Show at most this many tasks
+        #[arg(long)]
+        limit: Option<usize>,
+        /// Show the most recently added tasks first
+        #[arg(long)]
+        reverse: bool,
+    },
+    /// Show the tasks of today and the tasks due today from other days
+    Agenda {
+        /// Only read the day files of this many past days (default: `history_scan_limit`)
+        #[arg(long)]
+        scan_limit: Option<u32>,
+    },
+    /// Show the completion of each day of the current week
+    Week,
+    /// List the tags used in recent days with their task counts
+    Tags {
+        /// Number of days to scan, counting back from today
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+        /// Only list this tag and its subtags, like `+work` for `+work/projectA`
+        tag: Option<String>,
+        /// Also count the tasks under each parent of a `/` separated tag
+        #[arg(long)]
+        rollup: bool,
+        /// Also count the completed tasks deleted with `retain_completed_history`
+        #[arg(long)]
+        include_history: bool,
+    },
+    /// Show the average completion of each weekday
+    WeekdayStats {
+        /// Number of weeks to scan, counting back from today
+        #[arg(long, default_value_t = 8)]
+        weeks: u32,
+    },
+    /// Show the tasks added, removed or changed between two days
+    Diff {
+        /// Older day: `today`, `yesterday`, `tomorrow` or a date
+        from: String,
+        /// Newer day: `today`, `yesterday`, `tomorrow` or a date
+        to: String,
+    },
+    /// Create the files of the next days, seeded with the habits
+    Plan {
+        /// Number of days to create, starting tomorrow
+        #[arg(long, default_value_t = 7)]
+        days: u32,
+    },
+    /// Show a calendar of the days of a year where a habit was done
+    Heatmap {
+        /// Text of the habit, ignoring case
+        #[arg(long)]
+        habit: String,
+        /// Year to show (default: the current year)
+        #[arg(long)]
+        year: Option<i32>,
+    },
+    /// List the tasks added since a day, from their `created:` token
+    New {
+        /// First day to list: `today`, `yesterday`, `tomorrow` or a date
+        #[arg(long)]
+        since: String,
+    },
+    /// Move every pending task of a day to today
+    Pull {
+        /// Day to take the tasks from: `yesterday` or a date
+        #[arg(long)]
+        from: String,
+        /// Move without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Delete day files that hold nothing
+    Prune {
+        /// Delete the day files with no content but blank lines
+        #[arg(long)]
+        empty: bool,
+        /// Delete without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Print the path of a day file, without reading or creating it
+    Path {
+        /// Day of the file: `today`, `yesterday`, `tomorrow` or a date
+        #[arg(short, long)]
+        date: Option<String>,
+    },
+    /// Print a day as markdown for sharing, grouped in done and pending tasks
+    Share {
+        /// Day to share: `today`, `yesterday`, `tomorrow` or a date
+        #[arg(short, long)]
+        date: Option<String>,
+        /// Only list the tasks that are done
+        #[arg(long)]
+        done_only: bool,
+        /// Copy to the clipboard instead of printing
+        #[arg(long)]
+        copy: bool,
+    },
+    /// Write the tasks of every day to stdout, reading one day file at a time
+    Export {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ExportFormat::Jsonl)]
+        format: ExportFormat,
+    },
+    /// Write the tasks with a `due:` date as an iCalendar feed of to-dos
+    Ics {
+        /// First due day to include: `today`, `yesterday`, `tomorrow` or a date
+        #[arg(long)]
+        from: Option<String>,
+        /// Last due day to include: `today`, `yesterday`, `tomorrow` or a date
+        #[arg(long)]
+        to: Option<String>,
+        /// Only read the day files of this many past days (default: `history_scan_limit`)
+        #[arg(long)]
+        scan_limit: Option<u32>,
+    },
+    /// Add tasks to a day
+    Add {
+        /// Text of the task to add
+        text: Option<String>,
+        /// Read tasks from stdin, one per line
+        #[arg(long, conflicts_with = "text")]
+        stdin: bool,
+        /// Day to add to: `today`, `yesterday`, `tomorrow` or a date (default: today)
+        #[arg(short, long)]
+        date: Option<String>,
+    },
+    /// Mark a task as done
+    Done {
+        /// Text of the task, or a unique part of it
+        text: String,
+        /// Day of the task: `today`, `yesterday`, `tomorrow` or a date (default: today)
+        #[arg(long)]
+        on: Option<String>,
+    },
+    /// Change the text of a task
+    Edit {
+        /// Position of the task in the day, starting from 1
+        #[arg(short, long)]
+        index: usize,
+        /// New text of the task
+        #[arg(short, long)]
+        text: String,
+        /// Day to edit: `today`, `yesterday`, `tomorrow` or a date (default: today)
+        #[arg(short, long)]
+        date: Option<String>,
+    },
+    /// Replace text within the tasks of a day
+    Replace {
+        /// Text to look for
+        from: String,
+        /// Replacement text
+        to: String,
+        /// Day to edit: `today`, `yesterday`, `tomorrow` or a date (default: today)
+        #[arg(short, long)]
+        date: Option<String>,
+    },
+    /// Manage the inbox of tasks not tied to a day
+    Inbox {
+        #[command(subcommand)]
+        action: InboxCommand,
+    },
+    /// Manage the someday/maybe list of ideas without a date
+    Someday {
+        #[command(subcommand)]
+        action: SomedayCommand,
+    },
+    /// Normalize the formatting of the tasks in a day file
+    Fix {
+        /// Day to fix: `today`, `yesterday`, `tomorrow` or a date (default: today)
+        #[arg(short, long)]
+        date: Option<String>,
+    },
+    /// Report invalid lines, duplicate tasks and misnamed files in the tasks directory
+    Check,
+}
+
+/// Formats of the `export` subcommand
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ExportFormat {
+    /// One JSON object per task and per line
+    Jsonl,
+}
+
+/// Inbox subcommands
+#[derive(Subcommand, Debug)]
+pub enum InboxCommand {
+    /// Add a task to the inbox
+    Add {
+        /// Text of the task to add
+        text: String,
+    },
+    /// Show the tasks in the inbox
+    List,
+}
+
+/// Someday subcommands
+#[derive(Subcommand, Debug)]
+pub enum SomedayCommand {
+    /// Add a task to the someday list
+    Add {
+        /// Text of the task to add
+        text: String,
+    },
+    /// Show the tasks in the someday list
+    List,
+    /// Move a task from the someday list to a day
+    Move {
+        /// Text of the task, or a unique part of it
+        text: String,
+        /// Day to move to: `today`, `yesterday`, `tomorrow` or a date (default: today)
+        #[arg(short, long)]
+        date: Option<String>,
+    },
 }
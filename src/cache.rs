@@ -0,0 +1,103 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use crate::config::Config;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    /// Modification time of the day file, in nanoseconds since the epoch
+    modified: u64,
+    completed: usize,
+    #[serde(default)]
+    cancelled: usize,
+    /// Every task, cancelled ones included
+    total: usize,
+}
+
+/// Completion of each day, kept in `.stats-cache.json` in the tasks directory so
+/// summaries over a long history only reparse the days that changed
+#[derive(Debug, Default)]
+pub struct StatsCache {
+    /// Where the cache is stored; `None` when `stats_cache` is disabled
+    path: Option<PathBuf>,
+    entries: HashMap<String, Entry>,
+    dirty: bool,
+}
+
+impl StatsCache {
+    /// Load the cache, starting empty when it is missing or unreadable
+    pub fn load(config: &Config) -> StatsCache {
+        if !config.stats_cache {
+            return StatsCache::default();
+        }
+
+        let path = Path::new(&config.path).join(".stats-cache.json");
+        let entries = fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+
+        return StatsCache {
+            path: Some(path),
+            entries,
+            dirty: false,
+        };
+    }
+
+    /// The completed and total number of tasks of a day file, or `None` when it
+    /// does not exist
+    pub fn progress(&mut self, config: &Config, day_path: &Path) -> Result<Option<(usize, usize)>> {
+        let Ok(metadata) = fs::metadata(day_path) else {
+            return Ok(None);
+        };
+
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        let key = day_path.to_string_lossy().to_string();
+
+        if let Some(entry) = self.entries.get(&key).filter(|e| e.modified == modified) {
+            let total = crate::total(config, entry.total, entry.cancelled);
+            return Ok(Some((entry.completed, total)));
+        }
+
+        let Some(items) = crate::peek_items(day_path)? else {
+            return Ok(None);
+        };
+
+        let completed = items.iter().filter(|i| i.completed).count();
+        let cancelled = items.iter().filter(|i| i.cancelled).count();
+
+        if self.path.is_some() {
+            let entry = Entry {
+                modified,
+                completed,
+                cancelled,
+                total: items.len(),
+            };
+            self.entries.insert(key, entry);
+            self.dirty = true;
+        }
+
+        return Ok(Some((
+            completed,
+            crate::total(config, items.len(), cancelled),
+        )));
+    }
+
+    /// Write the cache back if anything was reparsed
+    pub fn save(&self) -> Result<()> {
+        if let (Some(path), true) = (&self.path, self.dirty) {
+            fs::write(path, serde_json::to_string(&self.entries)?)?;
+        }
+
+        return Ok(());
+    }
+}
@@ -1,6 +1,49 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
-use std::path::{Path, PathBuf};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// How the tasks are stored
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// One file per day
+    #[default]
+    Daily,
+    /// A single `todo.md` list, whatever the date
+    Single,
+}
+
+/// Which related tasks follow when a task is completed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Cascade {
+    /// Completing a task completes its subtasks
+    Down,
+    /// Completing the last open subtask completes its parent
+    Up,
+    /// Both of the above
+    Both,
+    /// Only the task itself
+    #[default]
+    None,
+}
+
+/// What the editing keys of the TUI do on the days before today
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EditPast {
+    /// Edit them like any other day
+    #[default]
+    Allow,
+    /// Ask for the key to be pressed again first
+    Confirm,
+    /// Keep them read-only
+    Deny,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
@@ -8,18 +51,130 @@ pub struct Config {
     pub path: String,
     #[serde(default = "default_date_format")]
     pub date_format: String,
+    #[serde(default)]
+    pub day_start_hour: u32,
     #[serde(default = "default_habits")]
     pub habits: Vec<String>,
+    #[serde(default)]
+    pub inbox_path: Option<String>,
+    #[serde(default)]
+    pub someday_path: Option<String>,
+    #[serde(default)]
+    pub save_on_focus_lost: bool,
+    #[serde(default)]
+    pub confirm_quit: bool,
+    #[serde(default)]
+    pub wip_limit: Option<usize>,
+    #[serde(default)]
+    pub wip_block_add: bool,
+    #[serde(default)]
+    pub skip_weekends: bool,
+    #[serde(default = "default_jump_days")]
+    pub jump_days: u32,
+    #[serde(default)]
+    pub high_contrast: bool,
+    #[serde(default = "default_pending_glyph")]
+    pub pending_glyph: String,
+    #[serde(default = "default_done_glyph")]
+    pub done_glyph: String,
+    #[serde(default = "default_cancelled_glyph")]
+    pub cancelled_glyph: String,
+    #[serde(default)]
+    pub count_cancelled: bool,
+    #[serde(default = "default_render_markdown")]
+    pub render_markdown: bool,
+    #[serde(default)]
+    pub show_path: bool,
+    #[serde(default)]
+    pub track_created: bool,
+    #[serde(default = "default_age_warn_days")]
+    pub age_warn_days: i64,
+    #[serde(default = "default_age_alert_days")]
+    pub age_alert_days: i64,
+    #[serde(default = "default_columns")]
+    pub columns: usize,
+    #[serde(default)]
+    pub on_complete: Option<String>,
+    #[serde(default = "default_bullet")]
+    pub bullet: char,
+    #[serde(default)]
+    pub goals_format: Option<String>,
+    #[serde(default)]
+    pub stats_cache: bool,
+    #[serde(default)]
+    pub mode: Mode,
+    #[serde(default = "default_large_file_items")]
+    pub large_file_items: usize,
+    #[serde(default)]
+    pub stay_in_insert: bool,
+    #[serde(default)]
+    pub pin_habits: bool,
+    #[serde(default)]
+    pub paste_split_lines: bool,
+    #[serde(default)]
+    pub max_tasks_per_day: Option<usize>,
+    #[serde(default)]
+    pub new_task_template: Option<String>,
+    #[serde(default)]
+    pub required: Vec<String>,
+    #[serde(default)]
+    pub retain_completed_history: bool,
+    #[serde(default)]
+    pub cascade: Cascade,
+    #[serde(default)]
+    pub edit_past: EditPast,
+    #[serde(default = "default_title_format")]
+    pub title_format: String,
+    #[serde(default)]
+    pub backup_dir: Option<String>,
+    #[serde(default)]
+    pub backup_keep: Option<usize>,
+    #[serde(default)]
+    pub line_numbers: bool,
+    #[serde(default)]
+    pub selection_symbol: String,
+    #[serde(default)]
+    pub resume_last_day: bool,
+    /// Footer message shown from each completion percentage up
+    #[serde(default)]
+    pub completion_messages: BTreeMap<u8, String>,
+    #[serde(default)]
+    pub keep_blank_lines: bool,
+    #[serde(default)]
+    pub keep_trailing_whitespace: bool,
+    /// Color of each tag, by name with or without its `@` or `+`
+    #[serde(default)]
+    pub tag_colors: BTreeMap<String, String>,
+    #[serde(default)]
+    pub track_carried: bool,
+    #[serde(default)]
+    pub history_scan_limit: Option<u32>,
+    /// Print and draw without colors or text attributes, for `NO_COLOR` or an
+    /// output that is not a terminal; set at startup, never read from the file
+    #[serde(skip)]
+    pub plain: bool,
+    #[serde(default)]
+    pub stamp_done: bool,
+    #[serde(default)]
+    pub stop_timer_on_done: bool,
+}
+
+fn home_dir() -> Result<PathBuf> {
+    return home::home_dir().context(
+        "Could not find the home directory; set `path` in a config file passed with --config",
+    );
 }
 
+/// Empty when the home directory cannot be resolved; `Config::validate` reports it
 fn default_path() -> String {
-    home::home_dir()
-        .expect("Could not find home directory")
-        .join(".config")
-        .join("todo")
-        .to_str()
-        .unwrap()
-        .to_string()
+    home_dir()
+        .map(|home| {
+            home.join(".config")
+                .join("todo")
+                .to_string_lossy()
+                .to_string()
+        })
+        .unwrap_or_default()
 }
 
 fn default_date_format() -> String {
@@ -30,18 +185,125 @@ fn default_habits() -> Vec<String> {
     vec![]
 }
 
+fn default_pending_glyph() -> String {
+    "☐".to_string()
+}
+
+fn default_done_glyph() -> String {
+    "☑".to_string()
+}
+
+fn default_cancelled_glyph() -> String {
+    "☒".to_string()
+}
+
+fn default_age_warn_days() -> i64 {
+    3
+}
+
+fn default_age_alert_days() -> i64 {
+    7
+}
+
+fn default_jump_days() -> u32 {
+    7
+}
+
+fn default_title_format() -> String {
+    "{date}".to_string()
+}
+
+fn default_large_file_items() -> usize {
+    1000
+}
+
+fn default_bullet() -> char {
+    '-'
+}
+
+fn default_columns() -> usize {
+    1
+}
+
+fn default_render_markdown() -> bool {
+    true
+}
+
 impl Default for Config {
     fn default() -> Self {
         Config {
             path: default_path(),
             date_format: default_date_format(),
+            day_start_hour: 0,
             habits: default_habits(),
+            inbox_path: None,
+            someday_path: None,
+            save_on_focus_lost: false,
+            confirm_quit: false,
+            wip_limit: None,
+            wip_block_add: false,
+            skip_weekends: false,
+            jump_days: default_jump_days(),
+            high_contrast: false,
+            pending_glyph: default_pending_glyph(),
+            done_glyph: default_done_glyph(),
+            cancelled_glyph: default_cancelled_glyph(),
+            count_cancelled: false,
+            render_markdown: default_render_markdown(),
+            show_path: false,
+            track_created: false,
+            age_warn_days: default_age_warn_days(),
+            age_alert_days: default_age_alert_days(),
+            columns: default_columns(),
+            on_complete: None,
+            bullet: default_bullet(),
+            goals_format: None,
+            stats_cache: false,
+            mode: Mode::Daily,
+            large_file_items: default_large_file_items(),
+            stay_in_insert: false,
+            pin_habits: false,
+            paste_split_lines: false,
+            max_tasks_per_day: None,
+            new_task_template: None,
+            required: Vec::new(),
+            retain_completed_history: false,
+            cascade: Cascade::None,
+            edit_past: EditPast::Allow,
+            title_format: default_title_format(),
+            backup_dir: None,
+            backup_keep: None,
+            line_numbers: false,
+            selection_symbol: String::new(),
+            resume_last_day: false,
+            completion_messages: BTreeMap::new(),
+            keep_blank_lines: false,
+            keep_trailing_whitespace: false,
+            tag_colors: BTreeMap::new(),
+            track_carried: false,
+            history_scan_limit: None,
+            plain: false,
+            stamp_done: false,
+            stop_timer_on_done: false,
         }
     }
 }
 
 impl Config {
+    /// Find and load the config, trying in order the `TODO_CONFIG` environment
+    /// variable, a `.todo.json` in the current directory and the XDG location,
+    /// falling back to the defaults
     pub fn parse() -> Result<Config> {
+        if let Ok(path) = std::env::var("TODO_CONFIG") {
+            return Config::from_file(&path)
+                .with_context(|| format!("Failed to load TODO_CONFIG file {}", path));
+        }
+
+        let project_path = Path::new(".todo.json");
+        if project_path.exists() {
+            return Config::from_file(project_path);
+        }
+
         let xdg_config_home = std::env::var("XDG_CONFIG_HOME")
             .map(PathBuf::from)
             .unwrap_or(
@@ -53,18 +315,128 @@ impl Config {
         let config_path = xdg_config_home.join("todo").join("config.json");
 
         if !config_path.exists() {
-            return Ok(Config::default());
+            return Config::default().merge_local(&config_path)?.validate();
         }
 
         return Config::from_file(&config_path);
     }
 
+    /// Load a config file, overlaid with its `.local.json` sibling when there is one
     pub fn from_file<P>(path: P) -> Result<Config>
     where
         P: AsRef<Path>,
     {
-        let config_file = std::fs::File::open(path)?;
+        let config_file = std::fs::File::open(&path)?;
+        let config: Config = serde_json::from_reader(config_file)?;
+
+        return config.merge_local(path)?.validate();
+    }
+
+    /// Overlay the fields set in `layer` on top of this config
+    ///
+    /// Fields missing from the layer keep their current value. When the layer sets
+    /// `append_habits` to `true`, its habits are added after the current ones
+    /// instead of replacing them.
+    pub fn merge(self, layer: Value) -> Result<Config> {
+        let Value::Object(layer) = layer else {
+            bail!("A config layer must be a JSON object");
+        };
+
+        let append_habits = layer
+            .get("append_habits")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+
+        let mut merged = serde_json::to_value(self)?;
+        for (key, value) in layer {
+            match (key.as_str(), value) {
+                ("append_habits", _) => {}
+                ("habits", Value::Array(habits)) if append_habits => {
+                    if let Some(Value::Array(current)) = merged.get_mut("habits") {
+                        current.extend(habits);
+                    }
+                }
+                (_, value) => merged[key] = value,
+            }
+        }
+
+        return Ok(serde_json::from_value(merged)?);
+    }
+
+    /// Merge the machine-specific layer stored next to `path`, e.g. `config.local.json`
+    fn merge_local<P>(self, path: P) -> Result<Config>
+    where
+        P: AsRef<Path>,
+    {
+        let local_path = path.as_ref().with_extension("local.json");
+
+        if !local_path.exists() {
+            return Ok(self);
+        }
+
+        let local_file = std::fs::File::open(local_path)?;
+
+        return self.merge(serde_json::from_reader(local_file)?);
+    }
+
+    /// The marker shown in the TUI for the state of an item
+    pub fn glyph(&self, item: &crate::item::Item) -> &str {
+        return match item.status() {
+            'x' => &self.done_glyph,
+            '~' => &self.cancelled_glyph,
+            _ => &self.pending_glyph,
+        };
+    }
+
+    /// The inbox file, `inbox.md` in the tasks directory unless configured
+    pub fn inbox_file(&self) -> PathBuf {
+        return match &self.inbox_path {
+            Some(path) => PathBuf::from(path),
+            None => Path::new(&self.path).join("inbox.md"),
+        };
+    }
+
+    /// The color of a tag: the one set in `tag_colors`, or one picked from its name
+    pub fn tag_color(&self, tag: &str) -> ratatui::style::Color {
+        let name = tag.trim_start_matches(['@', '+']);
+
+        return self
+            .tag_colors
+            .get(tag)
+            .or_else(|| self.tag_colors.get(name))
+            .and_then(|color| color.parse().ok())
+            .unwrap_or_else(|| crate::item::hashed_tag_color(name));
+    }
+
+    /// The someday/maybe list, `someday.md` in the tasks directory unless configured
+    pub fn someday_file(&self) -> PathBuf {
+        return match &self.someday_path {
+            Some(path) => PathBuf::from(path),
+            None => Path::new(&self.path).join("someday.md"),
+        };
+    }
+
+    /// Fail with a readable error when the defaults could not be resolved
+    fn validate(self) -> Result<Config> {
+        if self.path.is_empty() {
+            home_dir()?;
+            bail!("The `path` setting cannot be empty");
+        }
+
+        if !crate::item::BULLETS.contains(&self.bullet) {
+            bail!("The `bullet` setting must be one of `-`, `*` or `+`");
+        }
+
+        for (tag, color) in &self.tag_colors {
+            if color.parse::<ratatui::style::Color>().is_err() {
+                bail!(
+                    "Invalid color `{}` for the tag `{}` in `tag_colors`",
+                    color,
+                    tag
+                );
+            }
+        }
 
-        return serde_json::from_reader(config_file).map_err(|e| e.into());
+        return Ok(self);
     }
 }
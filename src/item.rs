@@ -0,0 +1,382 @@
+use anyhow::{bail, Error, Result};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use ratatui::style::Color;
+use std::{fmt, str::FromStr};
+
+/// List markers accepted before the checkbox when reading
+pub const BULLETS: [char; 3] = ['-', '*', '+'];
+
+/// Colors cycled through by the label keybind, in order
+const LABEL_COLORS: [Color; 6] = [
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+];
+
+/// A color for a tag picked from its name, so it stays the same between runs
+pub fn hashed_tag_color(tag: &str) -> Color {
+    let hash = tag.bytes().fold(0usize, |h, b| {
+        h.wrapping_mul(31).wrapping_add(usize::from(b))
+    });
+
+    return LABEL_COLORS[hash % LABEL_COLORS.len()];
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Item {
+    pub text: String,
+    pub completed: bool,
+    /// Dropped on purpose, written as `[~]`; never set together with `completed`
+    pub cancelled: bool,
+    /// Whether the item was seeded from `config.habits`; not persisted
+    pub is_habit: bool,
+    /// Whether its subtasks are hidden in the TUI; not persisted
+    pub folded: bool,
+    /// Manual color set with the `label:<color>` token
+    pub color: Option<Color>,
+    /// Recurrence interval in days set with the `every:<N>d` token
+    pub every: Option<u32>,
+    /// Day the item should be done by, set with the `due:<YYYY-MM-DD>` token
+    pub due: Option<NaiveDate>,
+    /// Time of day it is due at, from a `due:<YYYY-MM-DD>T<HH:MM>` token
+    pub due_time: Option<NaiveTime>,
+    /// Expected effort in minutes, set with the `est:<N>m` or `est:<N>h` token
+    pub estimate: Option<u32>,
+    /// Time tracked on it in minutes, kept in the `spent:<N>m` token
+    pub spent: Option<u32>,
+    /// Day the item was added, set with the `created:<YYYY-MM-DD>` token
+    pub created: Option<NaiveDate>,
+    /// When it was completed, set with `stamp_done` in the `done:<YYYY-MM-DD>T<HH:MM>` token
+    pub done_at: Option<NaiveDateTime>,
+    /// Number of times it was moved on to a later day, kept in the `carried:<N>` token
+    pub carried: Option<u32>,
+    /// Task this one waits for, by position or text, set with `blocked:<ref>`
+    pub blocked_by: Option<String>,
+    /// Nesting level of a subtask, stored as two spaces per level before the bullet
+    pub indent: usize,
+    /// The line it was read from and the line it rendered as then; the file keeps the
+    /// line as it was, token order and case included, until the item changes
+    pub read_as: Option<(String, String)>,
+    /// Whitespace at the end of its line, written back with `keep_trailing_whitespace`
+    pub trailing: String,
+    /// Blank lines after it in the file, written back with `keep_blank_lines`
+    pub blank_after: usize,
+    /// Markdown headings like `## Morning` right above it, opening its section,
+    /// with the blank lines that follow them (or start the file) as empty lines
+    pub headings: Vec<String>,
+    /// Headings after it with no task under them yet, and their blank lines;
+    /// only on the last item
+    pub trailing_headings: Vec<String>,
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}", self.line('-'));
+    }
+}
+
+impl FromStr for Item {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unindented = s.trim_start_matches([' ', '\t']);
+        let width = s[..s.len() - unindented.len()]
+            .chars()
+            .map(|c| if c == '\t' { 2 } else { 1 })
+            .sum::<usize>();
+        let s = unindented;
+
+        let Some(rest) = s.strip_prefix(BULLETS) else {
+            bail!("Invalid item format");
+        };
+
+        // HACK: This is a hack to parse todo items from a string.
+        let text = rest.get(5..).unwrap_or_default();
+
+        let mut item = match rest.get(..4) {
+            Some(" [ ]") => Item {
+                completed: false,
+                ..Default::default()
+            },
+            Some(" [x]") => Item {
+                completed: true,
+                ..Default::default()
+            },
+            Some(" [~]") => Item {
+                cancelled: true,
+                ..Default::default()
+            },
+            _ => bail!("Invalid item format"),
+        };
+
+        let body = text.trim_end();
+        item.set_text(body);
+        item.trailing = text[body.len()..].to_string();
+        item.indent = width / 2;
+
+        return Ok(item);
+    }
+}
+
+impl Item {
+    pub fn new(text: String) -> Self {
+        let mut item = Item::default();
+        item.set_text(&text);
+
+        return item;
+    }
+
+    /// The line of the item in a day file, with `bullet` as its list marker
+    pub fn line(&self, bullet: char) -> String {
+        return format!(
+            "{}{} [{}] {}",
+            "  ".repeat(self.indent),
+            bullet,
+            self.status(),
+            self.body()
+        );
+    }
+
+    /// The text followed by its tokens, as written after the checkbox
+    pub fn body(&self) -> String {
+        let mut body = self.text.clone();
+
+        if let Some(color) = self.color {
+            body.push_str(&format!(" label:{}", color.to_string().to_lowercase()));
+        }
+
+        if let Some(days) = self.every {
+            body.push_str(&format!(" every:{}d", days));
+        }
+
+        if let Some(blocker) = &self.blocked_by {
+            body.push_str(&format!(" blocked:{}", blocker));
+        }
+
+        match (self.due, self.due_time) {
+            (Some(due), Some(time)) => body.push_str(&format!(
+                " due:{}T{}",
+                due.format("%Y-%m-%d"),
+                time.format("%H:%M")
+            )),
+            (Some(due), None) => body.push_str(&format!(" due:{}", due.format("%Y-%m-%d"))),
+            _ => {}
+        }
+
+        if let Some(minutes) = self.estimate {
+            body.push_str(&format!(" est:{}", format_minutes(minutes)));
+        }
+
+        if let Some(minutes) = self.spent {
+            body.push_str(&format!(" spent:{}", format_minutes(minutes)));
+        }
+
+        if let Some(created) = self.created {
+            body.push_str(&format!(" created:{}", created.format("%Y-%m-%d")));
+        }
+
+        if let Some(done_at) = self.done_at {
+            body.push_str(&format!(" done:{}", done_at.format("%Y-%m-%dT%H:%M")));
+        }
+
+        if let Some(times) = self.carried {
+            body.push_str(&format!(" carried:{}", times));
+        }
+
+        return body;
+    }
+
+    /// Set the text of the item, moving any known tokens into their fields
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text
+            .split(' ')
+            .filter(|word| !self.parse_token(word))
+            .collect::<Vec<_>>()
+            .join(" ");
+    }
+
+    /// Store the value of a `key:value` token, returning whether the word was one
+    fn parse_token(&mut self, word: &str) -> bool {
+        let Some((key, value)) = word.split_once(':') else {
+            return false;
+        };
+
+        match key {
+            "label" => match value.parse::<Color>() {
+                Ok(color) => self.color = Some(color),
+                Err(_) => return false,
+            },
+            "every" => match value.strip_suffix('d').and_then(|n| n.parse().ok()) {
+                Some(days) if days > 0 => self.every = Some(days),
+                _ => return false,
+            },
+            "blocked" if !value.is_empty() => self.blocked_by = Some(value.to_string()),
+            "due" => match NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M") {
+                Ok(due) => {
+                    self.due = Some(due.date());
+                    self.due_time = Some(due.time());
+                }
+                Err(_) => match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+                    Ok(due) => self.due = Some(due),
+                    Err(_) => return false,
+                },
+            },
+            "est" => match parse_minutes(value) {
+                Some(minutes) if minutes > 0 => self.estimate = Some(minutes),
+                _ => return false,
+            },
+            "spent" => match parse_minutes(value) {
+                Some(minutes) => self.spent = Some(minutes),
+                None => return false,
+            },
+            "created" => match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+                Ok(created) => self.created = Some(created),
+                Err(_) => return false,
+            },
+            "done" => match NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M") {
+                Ok(done_at) => self.done_at = Some(done_at),
+                Err(_) => return false,
+            },
+            "carried" => match value.parse() {
+                Ok(times) if times > 0 => self.carried = Some(times),
+                _ => return false,
+            },
+            _ => return false,
+        }
+
+        return true;
+    }
+
+    /// Parse a task written in a sloppier style than the canonical `- [ ] text`
+    ///
+    /// Accepts `-`, `*` and `+` bullets, an uppercase `X`, and any amount of
+    /// whitespace around the bullet and checkbox.
+    pub fn parse_lenient(s: &str) -> Option<Self> {
+        let indent = &s[..s.len() - s.trim_start().len()];
+        let rest = s.trim_start().strip_prefix(BULLETS)?;
+        let rest = rest.trim_start().strip_prefix('[')?;
+        let (mark, text) = rest.split_once(']')?;
+
+        let status = match mark.trim() {
+            "" => "- [ ]",
+            "x" | "X" => "- [x]",
+            "~" => "- [~]",
+            _ => return None,
+        };
+
+        return format!("{}{} {}", indent, status, text.trim()).parse().ok();
+    }
+
+    /// Switch between pending and done; a cancelled item goes back to pending.
+    /// The `done:` stamp goes away whenever the item is no longer done
+    pub fn toggle(&mut self) {
+        if self.cancelled {
+            self.cancelled = false;
+        } else {
+            self.completed = !self.completed;
+        }
+        if !self.completed {
+            self.done_at = None;
+        }
+    }
+
+    /// Switch between cancelled and pending, dropping any `done:` stamp
+    pub fn cancel(&mut self) {
+        self.cancelled = !self.cancelled;
+        self.completed = false;
+        self.done_at = None;
+    }
+
+    /// Neither done nor cancelled
+    pub fn is_open(&self) -> bool {
+        return !self.completed && !self.cancelled;
+    }
+
+    /// The character between the checkbox brackets: ` `, `x` or `~`
+    pub fn status(&self) -> char {
+        return match (self.completed, self.cancelled) {
+            (true, _) => 'x',
+            (_, true) => '~',
+            _ => ' ',
+        };
+    }
+
+    /// The first `http://` or `https://` link found in the text
+    pub fn url(&self) -> Option<&str> {
+        return self
+            .text
+            .split_whitespace()
+            .find(|w| w.starts_with("http://") || w.starts_with("https://"));
+    }
+
+    /// The `@context` and `+project` tags used in the text
+    pub fn tags(&self) -> Vec<&str> {
+        return self
+            .text
+            .split_whitespace()
+            .filter(|w| w.len() > 1 && (w.starts_with('@') || w.starts_with('+')))
+            .collect();
+    }
+
+    /// When the item is due; a due date without a time lasts until the end of that day
+    pub fn due_at(&self) -> Option<NaiveDateTime> {
+        let end_of_day = NaiveTime::from_hms_opt(23, 59, 59).unwrap_or_default();
+
+        return self
+            .due
+            .map(|due| due.and_time(self.due_time.unwrap_or(end_of_day)));
+    }
+
+    /// Number of days between the creation of the item and `day`, if it is tracked
+    pub fn age(&self, day: NaiveDate) -> Option<i64> {
+        return self.created.map(|created| (day - created).num_days());
+    }
+
+    /// Move the label color to the next one in the palette, wrapping back to no color
+    pub fn cycle_color(&mut self) {
+        let next = match self.color {
+            None => Some(0),
+            Some(color) => LABEL_COLORS.iter().position(|&c| c == color).map(|i| i + 1),
+        };
+
+        self.color = next.and_then(|i| LABEL_COLORS.get(i).copied());
+    }
+}
+
+/// Minutes written like `90m`, or `2h` when they make whole hours
+fn format_minutes(minutes: u32) -> String {
+    if minutes > 0 && minutes.is_multiple_of(60) {
+        return format!("{}h", minutes / 60);
+    }
+
+    return format!("{}m", minutes);
+}
+
+/// Minutes from a `<N>m` or `<N>h` duration
+fn parse_minutes(value: &str) -> Option<u32> {
+    return match value.split_at(value.len().saturating_sub(1)) {
+        (n, "m") => n.parse::<u32>().ok(),
+        (n, "h") => n.parse::<u32>().ok().and_then(|n| n.checked_mul(60)),
+        _ => None,
+    };
+}
+
+/// Whether `tag` is `parent` or nested under it by `/` segments
+pub fn is_subtag(tag: &str, parent: &str) -> bool {
+    return tag
+        .strip_prefix(parent)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/') || parent.ends_with('/'));
+}
+
+/// `tag` and each of its parents, like `+work` and `+work/projectA` for
+/// `+work/projectA`
+pub fn tag_levels(tag: &str) -> Vec<&str> {
+    return tag
+        .match_indices('/')
+        .map(|(at, _)| &tag[..at])
+        .chain(std::iter::once(tag))
+        .collect();
+}
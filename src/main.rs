@@ -1,106 +1,355 @@
+#![allow(clippy::needless_return)]
+
 mod args;
+mod cache;
 mod config;
+mod item;
+mod markdown;
 
-use anyhow::{bail, Error, Result};
-use chrono::Days;
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, Days, Local, NaiveDate, NaiveDateTime, Utc, Weekday};
 use clap::Parser;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{
+        self, DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste,
+        EnableFocusChange, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton,
+        MouseEventKind,
+    },
+    style::{StyledContent, Stylize},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
 use ratatui::{prelude::*, widgets::*};
 use std::{
+    collections::{HashMap, HashSet},
     fs::{self, OpenOptions},
-    io::{stdout, Read},
-    path::Path,
-    str::FromStr,
+    io::{stdin, stdout, BufRead, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
 };
 
-#[derive(Debug, Default)]
-pub struct Item {
-    text: String,
-    completed: bool,
+use item::Item;
+
+#[derive(Debug)]
+enum InputMode {
+    Normal,
+    Insert,
+    /// Adding subtasks under the item at the given index
+    InsertChild(usize),
+    /// Adding a task at the given index, with the given indent
+    InsertAt(usize, usize),
+    /// Typing the text to look for after `/`
+    Search,
+    /// Typing the position of the task to jump to after `:`
+    Goto,
+    /// Stepping through the pending tasks one at a time with `R`
+    Review,
+}
+
+impl Default for InputMode {
+    fn default() -> Self {
+        return InputMode::Normal;
+    }
+}
+
+/// An order the TUI shows the tasks in with `f`, while the file keeps the manual
+/// order until the sort is applied with `F`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortView {
+    /// Soonest due first, then the tasks without a due date
+    Due,
+    /// Pending first, then done, then cancelled
+    Status,
+    /// Alphabetical, ignoring case
+    Text,
 }
 
-impl ToString for Item {
-    fn to_string(&self) -> String {
-        let status = if self.completed { "- [x]" } else { "- [ ]" };
-        return format!("{} {}", status, self.text);
+impl SortView {
+    /// The view after `view`, back to the manual order after the last one
+    fn next(view: Option<SortView>) -> Option<SortView> {
+        return match view {
+            None => Some(SortView::Due),
+            Some(SortView::Due) => Some(SortView::Status),
+            Some(SortView::Status) => Some(SortView::Text),
+            Some(SortView::Text) => None,
+        };
+    }
+
+    fn name(self) -> &'static str {
+        return match self {
+            SortView::Due => "due date",
+            SortView::Status => "status",
+            SortView::Text => "text",
+        };
+    }
+}
+
+/// How `format_items` and `write_items` lay out and mirror a day file
+pub struct WriteOptions {
+    /// List marker written before the checkbox, from `bullet`
+    pub bullet: char,
+    /// Write back the blank lines between tasks read from the file
+    pub keep_blank_lines: bool,
+    /// Write back the trailing whitespace read from the file
+    pub keep_trailing_whitespace: bool,
+    /// Where every write is mirrored, from `backup_dir`
+    backup: Option<Backup>,
+}
+
+impl WriteOptions {
+    pub fn new(config: &config::Config) -> Self {
+        return WriteOptions {
+            bullet: config.bullet,
+            keep_blank_lines: config.keep_blank_lines,
+            keep_trailing_whitespace: config.keep_trailing_whitespace,
+            backup: config.backup_dir.as_ref().map(|dir| Backup {
+                root: PathBuf::from(&config.path),
+                dir: PathBuf::from(dir),
+                keep: config.backup_keep,
+            }),
+        };
     }
 }
 
-impl FromStr for Item {
-    type Err = Error;
+pub fn format_items(items: &[Item], options: &WriteOptions) -> String {
+    let blank_lines = options.keep_blank_lines;
+    let mut lines = Vec::new();
+
+    // The heading blocks hold their blank lines as empty ones
+    let block = |lines: &[String]| {
+        return lines
+            .iter()
+            .filter(|line| blank_lines || !line.is_empty())
+            .cloned()
+            .collect::<Vec<_>>();
+    };
+
+    for item in items {
+        lines.extend(block(&item.headings));
+
+        let mut line = match &item.read_as {
+            Some((read, rendered)) if *rendered == item.line('-') => read.clone(),
+            _ => item.line(options.bullet),
+        };
+        if options.keep_trailing_whitespace {
+            line.push_str(&item.trailing);
+        }
+        lines.push(line);
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if !s.starts_with("- [ ]") && !s.starts_with("- [x]") {
-            bail!("Invalid item format");
+        if blank_lines {
+            lines.extend(std::iter::repeat_n(String::new(), item.blank_after));
         }
+        lines.extend(block(&item.trailing_headings));
+    }
 
-        // HACK: This is a hack to parse todo items from a string.
-        let text = &s[6..];
+    // Editors end the last line with a newline, so keep it there too
+    if blank_lines && !lines.is_empty() {
+        lines.push(String::new());
+    }
 
-        match &s[..5] {
-            "- [ ]" => {
-                return Ok(Item {
-                    text: text.to_string(),
-                    completed: false,
-                })
-            }
-            "- [x]" => {
-                return Ok(Item {
-                    text: text.to_string(),
-                    completed: true,
-                })
-            }
-            _ => bail!("Invalid item format"),
+    return lines.join("\n");
+}
+
+/// The exact contents `write_items` writes for the items to the file at `path`
+pub fn render_items<P>(items: &[Item], path: P, options: &WriteOptions) -> String
+where
+    P: AsRef<Path>,
+{
+    let mut data = format_items(items, options);
+    let old = fs::read_to_string(&path).unwrap_or_default();
+
+    // Headings need an item to be kept on, so those of a file without tasks, or
+    // of a list whose last task is gone, stay at the top rather than being dropped.
+    // Otherwise the items hold every heading that is left.
+    if items
+        .iter()
+        .flat_map(|i| i.headings.iter().chain(&i.trailing_headings))
+        .all(|line| !is_heading(line))
+    {
+        let headings = old.lines().filter(|l| is_heading(l)).collect::<Vec<_>>();
+        let orphaned = items.is_empty() || !old.lines().any(|l| l.parse::<Item>().is_ok());
+        if !headings.is_empty() && orphaned {
+            data = [headings.join("\n"), data]
+                .into_iter()
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n");
         }
     }
+
+    // A final newline, as editors leave one, stays where it was
+    if old.ends_with('\n') && !data.is_empty() && !data.ends_with('\n') {
+        data.push('\n');
+    }
+
+    return data;
 }
 
-impl Item {
-    pub fn new(text: String) -> Self {
-        return Item {
-            text,
-            completed: false,
-        };
+pub fn write_items<P>(items: &[Item], path: P, options: &WriteOptions) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let data = render_items(items, &path, options);
+
+    // Nothing changed, so neither the file nor its backups are touched
+    let old = fs::read_to_string(&path).ok();
+    if old.as_ref() == Some(&data) {
+        return Ok(());
     }
 
-    pub fn toggle(&mut self) {
-        self.completed = !self.completed;
+    fs::write(&path, &data)?;
+    log::debug!(
+        "Wrote {} task(s) to {}",
+        items.len(),
+        path.as_ref().display()
+    );
+
+    // A day only visited is filled with its habits, which is not worth a copy
+    let created = old.as_ref().is_none_or(|old| old.is_empty());
+    if let Some(backup) = options.backup.as_ref().filter(|_| !created) {
+        backup.save(path.as_ref(), &data)?;
     }
+
+    return Ok(());
 }
 
-#[derive(Debug)]
-enum InputMode {
-    Normal,
-    Insert,
+struct Backup {
+    /// The tasks directory, whose layout is mirrored
+    root: PathBuf,
+    dir: PathBuf,
+    /// Number of copies kept for each file, `None` to keep them all
+    keep: Option<usize>,
 }
 
-impl Default for InputMode {
-    fn default() -> Self {
-        return InputMode::Normal;
+impl Backup {
+    /// Write a timestamped copy of the file, like `2024-05-30.20240530T101500.md`,
+    /// then drop the oldest copies beyond `keep`
+    fn save(&self, path: &Path, data: &str) -> Result<()> {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        let dir = self.dir.join(relative.parent().unwrap_or(Path::new("")));
+        let stem = relative
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let extension = relative
+            .extension()
+            .map(|e| format!(".{}", e.to_string_lossy()))
+            .unwrap_or_default();
+
+        fs::create_dir_all(&dir)?;
+        let stamp = Utc::now().format("%Y%m%dT%H%M%S");
+        fs::write(dir.join(format!("{}.{}{}", stem, stamp, extension)), data)?;
+
+        let Some(keep) = self.keep else {
+            return Ok(());
+        };
+
+        // The timestamps sort in the order the copies were made
+        let mut copies = fs::read_dir(&dir)?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|p| {
+                let name = p.file_name().unwrap_or_default().to_string_lossy();
+                let Some(rest) = name.strip_prefix(&format!("{}.", stem)) else {
+                    return false;
+                };
+                rest.strip_suffix(&extension)
+                    .is_some_and(|s| s.len() == 15 && s.as_bytes()[8] == b'T')
+            })
+            .collect::<Vec<_>>();
+        copies.sort();
+        for old in &copies[..copies.len().saturating_sub(keep)] {
+            fs::remove_file(old)?;
+        }
+
+        return Ok(());
+    }
+}
+
+/// Whether the line is a markdown heading such as `## Morning`, which opens a section
+pub fn is_heading(line: &str) -> bool {
+    let rest = line.trim_start_matches('#');
+    let level = line.len() - rest.len();
+
+    return (1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(' '));
+}
+
+/// Parse the tasks of a day file, logging the lines that are not tasks
+fn parse_lines(path: &Path, data: &str) -> Vec<Item> {
+    let mut items: Vec<Item> = Vec::new();
+    let mut headings = Vec::new();
+    let data = data.strip_suffix('\n').unwrap_or(data);
+
+    for (n, line) in data.split('\n').enumerate() {
+        // Blank lines after a heading, or before anything, stay with the headings
+        if line.trim().is_empty() {
+            match items.last_mut() {
+                Some(last) if headings.is_empty() => last.blank_after += 1,
+                _ => headings.push(String::new()),
+            }
+            continue;
+        }
+
+        if is_heading(line) {
+            headings.push(line.trim_end().to_string());
+            continue;
+        }
+
+        match line.parse::<Item>() {
+            Ok(mut item) => {
+                item.headings = std::mem::take(&mut headings);
+                item.read_as = Some((line.trim_end().to_string(), item.line('-')));
+                items.push(item);
+            }
+            Err(_) => {
+                log::warn!(
+                    "{}:{}: skipped, not a task: {}",
+                    path.display(),
+                    n + 1,
+                    line
+                );
+            }
+        }
+    }
+
+    // Headings at the end of the file open sections with no task yet; without any
+    // task, `write_items` keeps them from the file
+    if let Some(last) = items.last_mut() {
+        last.trailing_headings = headings;
     }
+
+    return items;
 }
 
-pub fn write_items<P>(items: &Vec<Item>, path: P) -> Result<()>
+/// Whether saving `items` would change the file, e.g. after it was edited elsewhere
+pub fn is_dirty<P>(items: &[Item], path: P, options: &WriteOptions) -> bool
 where
     P: AsRef<Path>,
 {
-    fs::write(
-        path,
-        items
-            .iter()
-            .map(|i| i.to_string())
-            .collect::<Vec<_>>()
-            .join("\n"),
-    )?;
+    return fs::read_to_string(&path)
+        .map(|data| data != render_items(items, &path, options))
+        .unwrap_or(true);
+}
 
-    return Ok(());
+pub fn read_items<P>(path: P, default_items: &[String], options: &WriteOptions) -> Result<Vec<Item>>
+where
+    P: AsRef<Path>,
+{
+    let (items, warning) = read_items_with_warning(path, default_items, options)?;
+
+    if let Some(warning) = warning {
+        eprintln!("Warning: {}", warning);
+    }
+
+    return Ok(items);
 }
 
-pub fn read_items<P>(path: P, default_items: &Vec<String>) -> Result<Vec<Item>>
+/// Same as `read_items`, but hands back the warning for a file that was not valid
+/// UTF-8 instead of printing it
+pub fn read_items_with_warning<P>(
+    path: P,
+    default_items: &[String],
+    options: &WriteOptions,
+) -> Result<(Vec<Item>, Option<String>)>
 where
     P: AsRef<Path>,
 {
@@ -110,276 +359,4408 @@ where
         items.extend(default_items.iter().map(|i| Item::new(i.to_string())));
     }
 
-    let mut data = String::new();
+    if let Some(parent) = path.as_ref().parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut bytes = Vec::new();
 
     let _ = OpenOptions::new()
         .read(true)
         .write(true)
         .create(true)
+        .truncate(false)
         .open(&path)?
-        .read_to_string(&mut data)?;
+        .read_to_end(&mut bytes)?;
 
-    items.extend(data.lines().filter_map(|line| line.parse::<Item>().ok()));
+    let (data, warning) = decode_day(&path, bytes)?;
+    log::debug!("Read {}", path.as_ref().display());
 
-    write_items(&items, path)?;
+    items.extend(parse_lines(path.as_ref(), &data));
 
-    return Ok(items);
+    for item in items.iter_mut() {
+        item.is_habit = default_items.contains(&item.text);
+    }
+
+    // Reading alone must leave a file that needs no change as it is
+    if render_items(&items, &path, options) != data {
+        write_items(&items, path, options)?;
+    }
+
+    return Ok((items, warning));
 }
 
-pub fn date(offset: i64, format: &str) -> String {
-    if offset >= 0 {
-        chrono::Utc::now().checked_add_days(Days::new(offset.unsigned_abs()))
-    } else {
-        chrono::Utc::now().checked_sub_days(Days::new(offset.unsigned_abs()))
+/// Read a day for the TUI, moving the habits back to the top in their configured
+/// order when `pin_habits` is set
+fn read_day(
+    config: &config::Config,
+    path: &Path,
+    habits: &[String],
+) -> Result<(Vec<Item>, Option<String>)> {
+    let options = WriteOptions::new(config);
+    let (mut items, warning) = read_items_with_warning(path, habits, &options)?;
+
+    if config.pin_habits && pin_habits(&mut items, habits) {
+        write_items(&items, path, &options)?;
     }
-    .expect("Buy more bits")
-    .format(format)
-    .to_string()
+
+    return Ok((items, warning));
 }
 
-fn main() -> Result<()> {
-    let args = args::Args::parse();
-    let config = match args.config {
-        Some(path) => config::Config::from_file(&path)?,
-        None => config::Config::parse()?,
+/// Sort the top-level tasks so habits come first, in the order of `habits`, and the
+/// other tasks keep their order below; subtasks move with their parent
+///
+/// Returns whether anything moved.
+fn pin_habits(items: &mut Vec<Item>, habits: &[String]) -> bool {
+    let blocks = top_level_blocks(items);
+
+    let rank = |block: &std::ops::Range<usize>| {
+        let item = &items[block.start];
+        return habits
+            .iter()
+            .position(|h| item.indent == 0 && *h == item.text)
+            .unwrap_or(habits.len());
     };
+    let mut sorted = blocks.clone();
+    sorted.sort_by_key(rank);
 
-    fs::create_dir_all(&config.path)?;
+    if sorted == blocks {
+        return false;
+    }
 
-    match args.subcmd {
-        Some(args::SubCommand::Status) => status(config),
-        Some(args::SubCommand::Details) => details(config),
-        None => tui(config),
+    move_blocks(items, sorted);
+
+    return true;
+}
+
+/// The range of each top-level task with its subtasks, in order
+fn top_level_blocks(items: &[Item]) -> Vec<std::ops::Range<usize>> {
+    let mut blocks = Vec::new();
+    let mut start = 0;
+    while start < items.len() {
+        let end = subtree_end(items, start);
+        blocks.push(start..end);
+        start = end;
+    }
+
+    return blocks;
+}
+
+/// Rearrange the items to follow `blocks`, ranges that cover every item once
+fn move_blocks(items: &mut Vec<Item>, blocks: Vec<std::ops::Range<usize>>) {
+    reorder_items(items, |items| {
+        let mut old = std::mem::take(items)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        for block in blocks {
+            items.extend(old[block].iter_mut().filter_map(Option::take));
+        }
+    });
+}
+
+/// The top-level blocks in the order of `view`; ties keep their manual order
+fn sorted_blocks(items: &[Item], view: SortView) -> Vec<std::ops::Range<usize>> {
+    let mut blocks = top_level_blocks(items);
+    match view {
+        SortView::Due => {
+            blocks.sort_by_key(|b| (items[b.start].due_at().is_none(), items[b.start].due_at()))
+        }
+        SortView::Status => blocks.sort_by_key(|b| match items[b.start].status() {
+            ' ' => 0,
+            'x' => 1,
+            _ => 2,
+        }),
+        SortView::Text => blocks.sort_by_key(|b| items[b.start].text.to_lowercase()),
     }
+
+    return blocks;
 }
 
-fn status(config: config::Config) -> Result<()> {
-    let day_offset = 0;
-    let day_name = date(day_offset, &config.date_format);
-    let day_path = Path::new(&config.path).join(format!("{}.md", day_name));
+/// Decode the contents of a day file, replacing invalid UTF-8
+///
+/// The original bytes of an invalid file are copied to `<file>.bak`, since they are
+/// lost once the day is saved. Only the first copy is made, so later reads of a
+/// file that was not saved yet keep that one.
+fn decode_day<P>(path: P, bytes: Vec<u8>) -> Result<(String, Option<String>)>
+where
+    P: AsRef<Path>,
+{
+    let error = match String::from_utf8(bytes) {
+        Ok(data) => return Ok((data, None)),
+        Err(error) => error,
+    };
 
-    let items = read_items(&day_path, &config.habits)?;
+    let mut backup = path.as_ref().as_os_str().to_owned();
+    backup.push(".bak");
+    let kept = match Path::new(&backup).exists() {
+        true => "an earlier copy is kept in",
+        false => {
+            fs::write(&backup, error.as_bytes())?;
+            "the original saved to"
+        }
+    };
 
-    let completed = items.iter().filter(|i| i.completed).count();
-    let total = items.len();
+    let warning = format!(
+        "{} is not valid UTF-8; invalid bytes were replaced and {} {}",
+        path.as_ref().display(),
+        kept,
+        Path::new(&backup).display()
+    );
 
-    println!("{}/{}", completed, total);
+    return Ok((
+        String::from_utf8_lossy(error.as_bytes()).to_string(),
+        Some(warning),
+    ));
+}
 
-    return Ok(());
+/// Read the items of a day file without creating or rewriting it
+///
+/// Returns `None` when the file does not exist.
+pub fn peek_items<P>(path: P) -> Result<Option<Vec<Item>>>
+where
+    P: AsRef<Path>,
+{
+    if !path.as_ref().exists() {
+        return Ok(None);
+    }
+
+    let data = String::from_utf8_lossy(&fs::read(&path)?).to_string();
+
+    return Ok(Some(parse_lines(path.as_ref(), &data)));
 }
 
-fn details(config: config::Config) -> Result<()> {
-    let day_offset = 0;
-    let day_name = date(day_offset, &config.date_format);
-    let day_path = Path::new(&config.path).join(format!("{}.md", day_name));
+/// Clears the colors and text attributes drawn under it, for plain mode
+struct Unstyled;
 
-    let items = read_items(&day_path, &config.habits)?;
+impl Widget for Unstyled {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        buf.set_style(area, Style::reset());
+    }
+}
 
-    for item in items {
-        println!("{}", item.to_string());
+/// The styled text for the terminal, or just its text in plain mode
+pub fn styled<D>(config: &config::Config, content: StyledContent<D>) -> String
+where
+    D: std::fmt::Display,
+{
+    if config.plain {
+        return content.content().to_string();
     }
 
-    return Ok(());
+    return content.to_string();
 }
 
-fn tui(config: config::Config) -> Result<()> {
-    let mut input_text = String::default();
-    let mut input_mode = InputMode::default();
-    let mut day_offset = 0;
-    let mut day_name = date(day_offset, &config.date_format);
-    let mut day_path = Path::new(&config.path).join(format!("{}.md", day_name));
-    let mut items = read_items(&day_path, &config.habits)?;
-    let mut items_state = ListState::default();
+/// The current local time, shifted back so that hours before `day_start_hour`
+/// still belong to the previous day
+pub fn now(config: &config::Config) -> NaiveDateTime {
+    return Local::now().naive_local() - chrono::Duration::hours(i64::from(config.day_start_hour));
+}
 
-    enable_raw_mode()?;
-    stdout().execute(EnterAlternateScreen)?;
-    stdout().execute(EnableMouseCapture)?;
-    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+pub fn today(config: &config::Config) -> NaiveDate {
+    return now(config).date();
+}
 
-    loop {
-        terminal.draw(|f| {
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .margin(1)
-                .constraints([Constraint::Min(10), Constraint::Max(2), Constraint::Max(1)].as_ref())
-                .split(f.size());
-
-            let items = List::new(
-                items
-                    .iter()
-                    .map(|i| -> ListItem { ListItem::new(i.to_string()).style(Style::default()) })
-                    .collect::<Vec<_>>(),
-            )
-            .block(
-                Block::default()
-                    .title(day_name.clone())
-                    .borders(Borders::ALL),
-            )
-            .highlight_style(
-                Style::default()
-                    .bg(Color::DarkGray)
-                    .add_modifier(Modifier::BOLD),
-            );
+/// The calendar day `offset` days away from today
+pub fn offset_day(config: &config::Config, offset: i64) -> NaiveDate {
+    return today(config) + chrono::Duration::days(offset);
+}
 
-            let (msg, style) = match input_mode {
-                InputMode::Normal => (
-                    vec![
-                        Span::raw("Press "),
-                        Span::styled("q", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(" to exit, "),
-                        Span::styled("t", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(" to go to today, "),
-                        Span::styled("h", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(" to go yesterday, "),
-                        Span::styled("l", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(" to go tomorrow, "),
-                        Span::styled("k", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(" to move up, "),
-                        Span::styled("j", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(" to move down, "),
-                        Span::styled("x", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(" to toggle, "),
-                        Span::styled("a", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(" to add new todo, "),
-                        Span::styled("d", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(" to remove."),
-                    ],
-                    Style::default().add_modifier(Modifier::RAPID_BLINK),
-                ),
-                _ => (
-                    vec![
-                        Span::raw("Press "),
-                        Span::styled("Esc", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(" to stop editing, "),
-                        Span::styled("Enter", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(" to write the todo."),
-                    ],
-                    Style::default(),
-                ),
-            };
+/// Read the existing day files of the `days` days up to and including today
+///
+/// Returns the offset, name and items of each day that has a file. Rest days are
+/// skipped so they do not count as failures. With `deleted`, the completed tasks
+/// kept in the `.history` of a day are added back.
+pub fn read_history(
+    config: &config::Config,
+    days: u32,
+    deleted: bool,
+) -> Result<Vec<(i64, String, Vec<Item>)>> {
+    let mut history = Vec::new();
 
-            let mut text = Text::from(Line::from(msg));
-            text.patch_style(style);
+    for offset in (1 - i64::from(days))..=0 {
+        let day_name = date(config, offset, &config.date_format);
+        let day_path = day_file(config, &day_name);
 
-            let help_message = Paragraph::new(text).wrap(Wrap { trim: true });
+        if is_rest_day(&day_path) {
+            continue;
+        }
 
-            f.render_stateful_widget(items, chunks[0], &mut items_state);
-            f.render_widget(help_message, chunks[1]);
+        let retained = match deleted {
+            true => peek_items(history_file(&day_path))?,
+            false => None,
+        };
 
-            match input_mode {
-                InputMode::Normal => {}
-                _ => {
-                    let p = Paragraph::new(Span::raw(input_text.as_str()));
-                    f.render_widget(p, chunks[2]);
-                    f.set_cursor(chunks[2].x + input_text.len() as u16, chunks[2].y);
-                }
+        match (peek_items(&day_path)?, retained) {
+            (Some(mut items), Some(retained)) => {
+                items.extend(retained);
+                history.push((offset, day_name, items));
             }
-        })?;
+            (Some(items), None) | (None, Some(items)) => history.push((offset, day_name, items)),
+            (None, None) => {}
+        }
+    }
 
-        if event::poll(std::time::Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
-                match input_mode {
-                    InputMode::Normal => match key.code {
-                        KeyCode::Char('q') => {
-                            write_items(&items, &day_path)?;
-                            break;
-                        }
-                        KeyCode::Char('t') => {
-                            write_items(&items, &day_path)?;
+    return Ok(history);
+}
 
-                            day_offset = 0;
-                            day_name = date(day_offset, &config.date_format);
-                            day_path = Path::new(&config.path).join(format!("{}.md", day_name));
-                            items = read_items(&day_path, &config.habits)?;
-                            items_state = ListState::default();
-                        }
-                        KeyCode::Char('h') => {
-                            write_items(&items, &day_path)?;
+pub fn date(config: &config::Config, offset: i64, format: &str) -> String {
+    if offset >= 0 {
+        now(config).checked_add_days(Days::new(offset.unsigned_abs()))
+    } else {
+        now(config).checked_sub_days(Days::new(offset.unsigned_abs()))
+    }
+    .expect("Buy more bits")
+    .format(format)
+    .to_string()
+}
 
-                            day_offset -= 1;
-                            day_name = date(day_offset, &config.date_format);
-                            day_path = Path::new(&config.path).join(format!("{}.md", day_name));
-                            items = read_items(&day_path, &config.habits)?;
-                            items_state = ListState::default();
-                        }
-                        KeyCode::Char('l') => {
-                            write_items(&items, &day_path)?;
+/// Move a day offset by `step` days, optionally jumping over Saturdays and Sundays
+pub fn step_offset(config: &config::Config, offset: i64, step: i64) -> i64 {
+    let is_weekend = |offset: i64| {
+        matches!(
+            offset_day(config, offset).weekday(),
+            Weekday::Sat | Weekday::Sun
+        )
+    };
 
-                            day_offset += 1;
-                            day_name = date(day_offset, &config.date_format);
-                            day_path = Path::new(&config.path).join(format!("{}.md", day_name));
-                            items = read_items(&day_path, &config.habits)?;
-                            items_state = ListState::default();
-                        }
-                        KeyCode::Char('j') => {
-                            if items.len() > 0 {
-                                let i = match items_state.selected() {
-                                    Some(i) => (i + 1) % items.len(),
-                                    None => 0,
-                                };
+    let mut offset = offset + step;
+    while config.skip_weekends && is_weekend(offset) {
+        offset += step.signum();
+    }
 
-                                items_state.select(Some(i));
-                            }
-                        }
-                        KeyCode::Char('k') => {
-                            if items.len() > 0 {
-                                let i = match items_state.selected() {
-                                    Some(i) => (i + items.len() - 1) % items.len(),
-                                    None => items.len() - 1,
-                                };
+    return offset;
+}
 
-                                items_state.select(Some(i));
-                            }
-                        }
-                        KeyCode::Char('x') => {
-                            if let Some(i) = items_state.selected() {
-                                items[i].toggle();
-                            }
+/// Number of done tasks and total number of tasks, which leaves out the cancelled
+/// ones unless `count_cancelled` is set
+pub fn progress(config: &config::Config, items: &[Item]) -> (usize, usize) {
+    let completed = items.iter().filter(|i| i.completed).count();
+    let cancelled = items.iter().filter(|i| i.cancelled).count();
 
-                            write_items(&items, &day_path)?;
-                        }
-                        KeyCode::Char('a') => {
-                            input_mode = InputMode::Insert;
-                        }
-                        KeyCode::Char('d') => {
-                            if let Some(i) = items_state.selected() {
-                                items.remove(i);
+    return (completed, total(config, items.len(), cancelled));
+}
 
-                                if items.len() == 0 {
-                                    items_state.select(None);
-                                } else {
-                                    items_state.select(Some((i + items.len() - 1) % items.len()));
-                                }
-                            }
+/// Completion percentage of a day, `None` when it has no task to count
+fn completion_percent(config: &config::Config, items: &[Item]) -> Option<usize> {
+    let (completed, total) = progress(config, items);
 
-                            write_items(&items, &day_path)?;
-                        }
-                        _ => {}
-                    },
-                    InputMode::Insert => match key.code {
-                        KeyCode::Enter => {
-                            items.push(Item::new(input_text.drain(..).collect()));
-                            input_mode = InputMode::Normal;
+    return (total > 0).then(|| completed * 100 / total);
+}
 
-                            write_items(&items, &day_path)?;
-                        }
-                        KeyCode::Char(c) => input_text.push(c),
-                        KeyCode::Backspace => {
-                            input_text.pop();
-                        }
-                        KeyCode::Esc => {
-                            input_mode = InputMode::Normal;
-                        }
-                        _ => {}
-                    },
-                }
-            }
-        }
+/// The `completion_messages` entry with the highest threshold the day reached
+fn completion_message<'a>(config: &'a config::Config, items: &[Item]) -> Option<&'a str> {
+    let percent = completion_percent(config, items)?;
+
+    return config
+        .completion_messages
+        .range(..=u8::try_from(percent).unwrap_or(u8::MAX))
+        .next_back()
+        .map(|(_, message)| message.as_str());
+}
+
+/// Total number of tasks out of `len`, given how many are cancelled
+pub fn total(config: &config::Config, len: usize, cancelled: usize) -> usize {
+    if config.count_cancelled {
+        return len;
     }
 
-    disable_raw_mode()?;
-    stdout().execute(LeaveAlternateScreen)?;
-    stdout().execute(DisableMouseCapture)?;
+    return len - cancelled;
+}
 
-    Ok(())
+/// Short marker for a neighbouring day: its completion ratio, or `-` when it has no file
+fn adjacent_marker(config: &config::Config, offset: i64) -> String {
+    let day_name = date(config, offset, &config.date_format);
+    let day_path = day_file(config, &day_name);
+
+    if is_rest_day(&day_path) {
+        return "rest".to_string();
+    }
+
+    return match peek_items(day_path).ok().flatten() {
+        Some(items) => {
+            let (completed, total) = progress(config, &items);
+            format!("{}/{}", completed, total)
+        }
+        None => "-".to_string(),
+    };
+}
+
+/// Add an uncompleted copy of a recurring item to the day `every` days after `offset`
+///
+/// Returns the name of that day and the warning from reading it, or `None` when the
+/// item does not recur or the day already has a pending copy of it.
+fn schedule_next(
+    config: &config::Config,
+    offset: i64,
+    item: &Item,
+) -> Result<Option<(String, Option<String>)>> {
+    let Some(days) = item.every else {
+        return Ok(None);
+    };
+    if config.mode == config::Mode::Single {
+        return Ok(None);
+    }
+
+    let options = WriteOptions::new(config);
+    let day_name = date(config, offset + i64::from(days), &config.date_format);
+    let path = day_file(config, &day_name);
+    let (mut items, warning) = read_items_with_warning(&path, &config.habits, &options)?;
+
+    if items.iter().any(|i| i.text == item.text && i.is_open()) {
+        return Ok(None);
+    }
+
+    let mut next: Item = item.to_string().parse()?;
+    next.completed = false;
+    next.done_at = None;
+    next.spent = None;
+    next.created = item.created.map(|_| today(config));
+    push_item(&mut items, next);
+
+    write_items(&items, &path, &options)?;
+
+    return Ok(Some((day_name, warning)));
+}
+
+/// Append items to the day at `offset`, returning the name of that day and the
+/// warning from reading it, for the caller to show
+fn move_to_day(
+    config: &config::Config,
+    offset: i64,
+    moved: Vec<Item>,
+) -> Result<(String, Option<String>)> {
+    let options = WriteOptions::new(config);
+    let day_name = date(config, offset, &config.date_format);
+    let path = day_file(config, &day_name);
+
+    let (mut items, warning) = read_items_with_warning(&path, &config.habits, &options)?;
+    for item in moved {
+        push_item(&mut items, item);
+    }
+    write_items(&items, &path, &options)?;
+
+    return Ok((day_name, warning));
+}
+
+/// Count one more carry-over on tasks moved on from a day, with `track_carried`
+fn mark_carried(config: &config::Config, items: &mut [Item]) {
+    if !config.track_carried {
+        return;
+    }
+
+    for item in items {
+        item.carried = Some(item.carried.unwrap_or(0) + 1);
+    }
+}
+
+/// Index of the pending task that blocks the item at `index`, if any
+///
+/// The `blocked:` reference is either a 1-based position in the day or a part of
+/// the blocking task's text.
+pub fn blocker(items: &[Item], index: usize) -> Option<usize> {
+    let reference = items[index].blocked_by.as_ref()?;
+
+    let found = match reference.parse::<usize>() {
+        Ok(position) => position.checked_sub(1).filter(|&i| i < items.len()),
+        Err(_) => {
+            let needle = reference.to_lowercase();
+            items
+                .iter()
+                .enumerate()
+                .position(|(j, i)| j != index && i.text.to_lowercase().contains(&needle))
+        }
+    };
+
+    return found.filter(|&b| b != index && items[b].is_open());
+}
+
+/// Toggle the item at `index` and apply what follows a completion
+///
+/// Returns a note for the footer, if there is anything to tell.
+fn toggle_item(
+    config: &config::Config,
+    offset: i64,
+    items: &mut [Item],
+    index: usize,
+) -> Result<Option<String>> {
+    if !items[index].completed {
+        if let Some(b) = blocker(items, index) {
+            return Ok(Some(format!("Blocked by '{}'", items[b].text)));
+        }
+    }
+
+    items[index].toggle();
+
+    items[index].done_at = match items[index].completed && config.stamp_done {
+        true => Some(Utc::now().naive_utc()),
+        false => None,
+    };
+
+    if items[index].completed {
+        cascade(config, items, index);
+        run_on_complete(config, &items[index]);
+
+        if let Some((next, warning)) = schedule_next(config, offset, &items[index])? {
+            return Ok(Some(
+                warning.unwrap_or(format!("Scheduled the next occurrence on {}", next)),
+            ));
+        }
+    }
+
+    return Ok(None);
+}
+
+/// Complete the subtasks or the parents of the just completed item at `index`, as
+/// `cascade` says
+fn cascade(config: &config::Config, items: &mut [Item], index: usize) {
+    use config::Cascade;
+
+    if matches!(config.cascade, Cascade::Down | Cascade::Both) {
+        let end = subtree_end(items, index);
+        for child in &mut items[index + 1..end] {
+            if child.is_open() {
+                child.completed = true;
+            }
+        }
+    }
+
+    if matches!(config.cascade, Cascade::Up | Cascade::Both) {
+        let mut index = index;
+        while let Some(parent) = (0..index)
+            .rev()
+            .find(|&i| items[i].indent < items[index].indent)
+        {
+            let children = &items[parent + 1..subtree_end(items, parent)];
+            if !items[parent].is_open() || children.iter().any(Item::is_open) {
+                break;
+            }
+
+            items[parent].completed = true;
+            index = parent;
+        }
+    }
+}
+
+/// Start the `on_complete` command for a completed item, without waiting for it
+///
+/// `{text}` in the command is replaced by the quoted text of the task. Failures are
+/// ignored so a broken hook never gets in the way.
+fn run_on_complete(config: &config::Config, item: &Item) {
+    let Some(command) = &config.on_complete else {
+        return;
+    };
+
+    let quoted = format!("'{}'", item.text.replace('\'', "'\\''"));
+    let child = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command.replace("{text}", &quoted))
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn();
+
+    // Reap it in the background so it does not linger as a zombie
+    match child {
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => log::warn!("Failed to run on_complete: {}", e),
+    }
+}
+
+/// Warn about a day with more tasks than `max_tasks_per_day`, e.g. after seeding
+/// habits, or than `large_file_items`, usually pasted content or a runaway script
+fn size_warning(config: &config::Config, items: &[Item]) -> Option<String> {
+    if let Some(max) = config.max_tasks_per_day.filter(|&max| items.len() > max) {
+        return Some(format!(
+            "This day has {} tasks, more than the max_tasks_per_day of {}",
+            items.len(),
+            max
+        ));
+    }
+
+    if items.len() <= config.large_file_items {
+        return None;
+    }
+
+    return Some(format!(
+        "This list has {} tasks, more than the {} of large_file_items",
+        items.len(),
+        config.large_file_items
+    ));
+}
+
+/// The footer note when the day already holds `max_tasks_per_day` tasks
+fn task_limit_note(config: &config::Config, items: &[Item]) -> Option<String> {
+    let max = config.max_tasks_per_day.filter(|&max| items.len() >= max)?;
+
+    return Some(format!(
+        "This day already has the max_tasks_per_day of {}; focus on those first",
+        max
+    ));
+}
+
+/// The footer note when no task may be added to the list at `path`: the day is
+/// at `max_tasks_per_day`, or `wip_block_add` holds it at `wip_limit`
+fn add_block_note(config: &config::Config, items: &[Item], path: &Path) -> Option<String> {
+    if let Some(note) = task_limit_note(config, items).filter(|_| !is_undated(config, path)) {
+        return Some(note);
+    }
+
+    let limit = config
+        .wip_limit
+        .filter(|&limit| config.wip_block_add && pending_count(items) >= limit)?;
+
+    return Some(format!(
+        "Work in progress limit of {} reached; complete a task first",
+        limit
+    ));
+}
+
+/// The heading of a day in the TUI, from `title_format`
+fn title_text(config: &config::Config, day_name: &str, offset: i64, items: &[Item]) -> String {
+    let (done, total) = progress(config, items);
+    let relative = match offset {
+        0 => "today".to_string(),
+        -1 => "yesterday".to_string(),
+        1 => "tomorrow".to_string(),
+        _ => format!("{:+}d", offset),
+    };
+
+    return config
+        .title_format
+        .replace("{date}", day_name)
+        .replace(
+            "{weekday}",
+            &offset_day(config, offset).format("%A").to_string(),
+        )
+        .replace("{offset}", &relative)
+        .replace("{done}", &done.to_string())
+        .replace("{total}", &total.to_string());
+}
+
+/// Markers for the previous and next day, following the same steps as `h` and `l`
+fn adjacent_markers(config: &config::Config, offset: i64) -> (String, String) {
+    return (
+        adjacent_marker(config, step_offset(config, offset, -1)),
+        adjacent_marker(config, step_offset(config, offset, 1)),
+    );
+}
+
+/// Find the item matching `query`: exactly, or else as a unique case-insensitive part
+pub fn find_item(items: &[Item], query: &str) -> Result<usize> {
+    if let Some(i) = items.iter().position(|i| i.text == query) {
+        return Ok(i);
+    }
+
+    let needle = query.to_lowercase();
+    let matches = items
+        .iter()
+        .enumerate()
+        .filter(|(_, i)| i.text.to_lowercase().contains(&needle))
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+
+    return match matches[..] {
+        [i] => Ok(i),
+        [] => bail!("No task matches '{}'", query),
+        _ => bail!(
+            "{} tasks match '{}'; be more specific",
+            matches.len(),
+            query
+        ),
+    };
+}
+
+/// The `required` tasks that are not done in the day, missing ones included
+pub fn unmet_required<'a>(config: &'a config::Config, items: &[Item]) -> Vec<&'a str> {
+    return config
+        .required
+        .iter()
+        .filter(|text| !items.iter().any(|i| i.completed && &i.text == *text))
+        .map(String::as_str)
+        .collect();
+}
+
+pub fn pending_count(items: &[Item]) -> usize {
+    return items.iter().filter(|i| i.is_open()).count();
+}
+
+/// Whether the day has more pending tasks than the configured `wip_limit`
+pub fn over_wip_limit(config: &config::Config, items: &[Item]) -> bool {
+    return config
+        .wip_limit
+        .is_some_and(|limit| pending_count(items) > limit);
+}
+
+/// Take the items in `range` out of the list, leaving their headings in place
+/// above the item that follows them
+pub fn remove_items(items: &mut Vec<Item>, range: std::ops::Range<usize>) -> Vec<Item> {
+    let at = range.start;
+    let mut removed = items.drain(range).collect::<Vec<_>>();
+
+    let mut headings = Vec::new();
+    for item in removed.iter_mut() {
+        headings.append(&mut item.headings);
+        headings.append(&mut item.trailing_headings);
+    }
+    match items.get_mut(at) {
+        Some(next) => {
+            headings.append(&mut next.headings);
+            next.headings = headings;
+        }
+        None => {
+            if let Some(last) = items.last_mut() {
+                last.trailing_headings.append(&mut headings);
+            }
+        }
+    }
+
+    return removed;
+}
+
+/// Insert an item at `index`, at the end of the section above when it starts
+/// another one there, and above the empty sections at the end of the list
+pub fn insert_item(items: &mut Vec<Item>, index: usize, mut item: Item) {
+    item.headings.clear();
+    item.trailing_headings.clear();
+
+    if index == items.len() {
+        if let Some(last) = items.last_mut() {
+            item.trailing_headings = std::mem::take(&mut last.trailing_headings);
+        }
+    }
+    items.insert(index, item);
+}
+
+/// Add an item after the last task, above the empty sections at the end
+pub fn push_item(items: &mut Vec<Item>, item: Item) {
+    insert_item(items, items.len(), item);
+}
+
+/// Reorder the items while their headings stay at the same positions
+pub fn reorder_items<F>(items: &mut Vec<Item>, reorder: F)
+where
+    F: FnOnce(&mut Vec<Item>),
+{
+    let headings = items
+        .iter_mut()
+        .map(|i| std::mem::take(&mut i.headings))
+        .collect::<Vec<_>>();
+    let trailing = items
+        .last_mut()
+        .map(|i| std::mem::take(&mut i.trailing_headings))
+        .unwrap_or_default();
+
+    reorder(items);
+
+    for (item, headings) in items.iter_mut().zip(headings) {
+        item.headings = headings;
+    }
+    if let Some(last) = items.last_mut() {
+        last.trailing_headings = trailing;
+    }
+}
+
+/// Index right after the last task of the section of the item at `index`
+pub fn section_end(items: &[Item], index: usize) -> usize {
+    return items[index + 1..]
+        .iter()
+        .position(|i| i.headings.iter().any(|h| is_heading(h)))
+        .map_or(items.len(), |p| index + 1 + p);
+}
+
+/// Index right after the last subtask of the item at `index`
+pub fn subtree_end(items: &[Item], index: usize) -> usize {
+    let indent = items[index].indent;
+
+    return items[index + 1..]
+        .iter()
+        .position(|i| i.indent <= indent)
+        .map_or(items.len(), |p| index + 1 + p);
+}
+
+/// Remove the pending tasks that are not habits, each with its subtasks
+///
+/// Subtasks of a finished task are taken on their own, at the top level.
+fn take_pending(items: &mut Vec<Item>) -> Vec<Item> {
+    let mut taken = Vec::new();
+    let mut i = 0;
+
+    while i < items.len() {
+        if items[i].status() != ' ' || items[i].is_habit {
+            i += 1;
+            continue;
+        }
+
+        let base = items[i].indent;
+        let end = subtree_end(items, i);
+        taken.extend(remove_items(items, i..end).into_iter().map(|mut item| {
+            item.indent -= base;
+            item
+        }));
+    }
+
+    return taken;
+}
+
+/// The first pending task from `from` on that is not a habit or folded away, for the
+/// review mode
+fn next_to_review(items: &[Item], from: usize) -> Option<usize> {
+    return visible_items(items)
+        .into_iter()
+        .find(|&i| i >= from && items[i].status() == ' ' && !items[i].is_habit);
+}
+
+/// Number of tasks the review mode still has to go through
+fn review_left(items: &[Item]) -> usize {
+    return items
+        .iter()
+        .filter(|i| i.status() == ' ' && !i.is_habit)
+        .count();
+}
+
+/// Indices of the items that are not hidden under a folded parent
+pub fn visible_items(items: &[Item]) -> Vec<usize> {
+    let mut visible = Vec::new();
+    let mut index = 0;
+
+    while index < items.len() {
+        visible.push(index);
+        index = if items[index].folded {
+            subtree_end(items, index)
+        } else {
+            index + 1
+        };
+    }
+
+    return visible;
+}
+
+/// The visible items in the order they are shown, sorted by `view` when there is one
+fn shown_items(items: &[Item], view: Option<SortView>) -> Vec<usize> {
+    let visible = visible_items(items);
+    let Some(view) = view else {
+        return visible;
+    };
+
+    return sorted_blocks(items, view)
+        .into_iter()
+        .flatten()
+        .filter(|i| visible.binary_search(i).is_ok())
+        .collect();
+}
+
+/// Resolve a `due:` phrase such as `tomorrow`, `+3d`, `+2w` or `friday` from `day`
+///
+/// Weekdays are the next one after `day`. Returns `None` for anything else, so
+/// the token is left as typed.
+fn resolve_due_phrase(phrase: &str, day: NaiveDate) -> Option<NaiveDate> {
+    let phrase = phrase.to_lowercase();
+    match phrase.as_str() {
+        "today" => return Some(day),
+        "tomorrow" => return day.succ_opt(),
+        "yesterday" => return day.pred_opt(),
+        _ => {}
+    }
+
+    if let Some(amount) = phrase.strip_prefix('+') {
+        let (n, unit) = amount.split_at(amount.len().saturating_sub(1));
+        let n = n.parse::<u64>().ok()?;
+        return match unit {
+            "d" => day.checked_add_days(Days::new(n)),
+            // Amounts past the range of dates are left unresolved too
+            "w" => day.checked_add_days(Days::new(n.checked_mul(7)?)),
+            _ => None,
+        };
+    }
+
+    let weekday = phrase.parse::<chrono::Weekday>().ok()?;
+    let ahead = (weekday.num_days_from_monday() + 7 - day.weekday().num_days_from_monday()) % 7;
+    return day.checked_add_days(Days::new(u64::from(if ahead == 0 { 7 } else { ahead })));
+}
+
+/// Rewrite the `due:` phrases of a typed task as concrete dates, relative to `day`
+fn resolve_due(text: &str, day: NaiveDate) -> String {
+    return text
+        .split(' ')
+        .map(|word| {
+            let resolved = word
+                .strip_prefix("due:")
+                .and_then(|phrase| resolve_due_phrase(phrase, day));
+            match resolved {
+                Some(due) => format!("due:{}", due.format("%Y-%m-%d")),
+                None => word.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+}
+
+/// Split the `@context` and `+project` tags out of the spans, in their color
+fn color_tags(config: &config::Config, spans: Vec<Span<'_>>) -> Vec<Span<'static>> {
+    let mut colored = Vec::new();
+
+    for span in spans {
+        let mut plain = String::new();
+        for word in span.content.split_inclusive(' ') {
+            let tag = word.trim_end();
+            if tag.len() > 1 && (tag.starts_with('@') || tag.starts_with('+')) {
+                if !plain.is_empty() {
+                    colored.push(Span::styled(std::mem::take(&mut plain), span.style));
+                }
+                let style = span.style.fg(config.tag_color(tag));
+                colored.push(Span::styled(tag.to_string(), style));
+                plain.push_str(&word[tag.len()..]);
+            } else {
+                plain.push_str(word);
+            }
+        }
+        if !plain.is_empty() {
+            colored.push(Span::styled(plain, span.style));
+        }
+    }
+
+    return colored;
+}
+
+/// A row of the list in the TUI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Row<'a> {
+    /// A section heading, shown as a divider that cannot be selected
+    Heading(&'a str),
+    Item(usize),
+    /// A task due today from another day, by its index in the tasks shown with `T`
+    Elsewhere(usize),
+}
+
+/// The rows of the visible items, with the headings of the sections in between
+///
+/// A sorted view mixes the sections, so it has no headings.
+fn list_rows<'a>(items: &'a [Item], visible: &[usize], view: Option<SortView>) -> Vec<Row<'a>> {
+    if view.is_some() {
+        return visible.iter().map(|&i| Row::Item(i)).collect();
+    }
+
+    let mut rows = Vec::new();
+
+    for (index, item) in items.iter().enumerate() {
+        let heading = |h: &&String| is_heading(h);
+        rows.extend(
+            item.headings
+                .iter()
+                .filter(heading)
+                .map(|h| Row::Heading(h)),
+        );
+        if visible.binary_search(&index).is_ok() {
+            rows.push(Row::Item(index));
+        }
+        rows.extend(
+            item.trailing_headings
+                .iter()
+                .filter(heading)
+                .map(|h| Row::Heading(h)),
+        );
+    }
+
+    return rows;
+}
+
+/// The task for the typed text, put into `new_task_template` first so the `due:`
+/// phrases of both are resolved from `due_base`
+fn typed_task(config: &config::Config, typed: &str, due_base: NaiveDate) -> String {
+    let text = match &config.new_task_template {
+        Some(template) => strip_list_marker(&template.replace("{}", typed)).to_string(),
+        None => typed.to_string(),
+    };
+
+    return resolve_due(&text, due_base);
+}
+
+/// Drop a leading bullet and checkbox, like the `- [ ] ` of a template written
+/// as a whole task line, since the task is written with its own
+fn strip_list_marker(text: &str) -> &str {
+    let text = text.trim_start();
+    // A bullet needs a space after it, so a `+tag` stays
+    let text = match text.strip_prefix(item::BULLETS) {
+        Some(rest) if rest.starts_with(' ') => rest.trim_start(),
+        _ => text,
+    };
+
+    return match text.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+        Some((mark, rest)) if ["", " ", "x", "X", "~"].contains(&mark) => rest.trim_start(),
+        _ => text,
+    };
+}
+
+/// Add the typed task where the insert mode says, leaving that mode unless `stay`
+fn submit_input(
+    config: &config::Config,
+    items: &mut Vec<Item>,
+    items_state: &mut ListState,
+    marked: &mut HashSet<usize>,
+    input_mode: &mut InputMode,
+    text: String,
+    stay: bool,
+) {
+    let mut item = Item::new(text);
+    if config.track_created {
+        item.created = Some(today(config));
+    }
+
+    match *input_mode {
+        InputMode::InsertChild(parent) => {
+            // Stay in this mode so the whole breakdown can be typed
+            item.indent = items[parent].indent + 1;
+            let at = subtree_end(items, parent);
+            insert_item(items, at, item);
+            items_state.select(Some(at));
+            marked.clear();
+        }
+        InputMode::InsertAt(at, indent) => {
+            item.indent = indent;
+            // Opened above the selection with `i`, so it goes under its heading too
+            let headings = match items_state.selected() == Some(at) {
+                true => std::mem::take(&mut items[at].headings),
+                false => Vec::new(),
+            };
+            insert_item(items, at, item);
+            items[at].headings = headings;
+            items_state.select(Some(at));
+            marked.clear();
+            *input_mode = if stay {
+                InputMode::InsertAt(at + 1, indent)
+            } else {
+                InputMode::Normal
+            };
+        }
+        _ => {
+            // At the end of the section of the selection, or of the list
+            let at = match items_state.selected() {
+                Some(i) => section_end(items, i),
+                None => items.len(),
+            };
+            insert_item(items, at, item);
+            marked.clear();
+            if !stay {
+                *input_mode = InputMode::Normal;
+            }
+        }
+    }
+}
+
+/// Add the time since the timer started to the `spent:` of its task and save it;
+/// the task may be on another day than the one shown in `items`
+fn stop_timer(
+    items: &mut [Item],
+    day_path: &Path,
+    timer: (PathBuf, String, std::time::Instant),
+    options: &WriteOptions,
+) -> Result<String> {
+    let (path, text, started) = timer;
+    let minutes = u32::try_from((started.elapsed().as_secs() + 30) / 60).unwrap_or(u32::MAX);
+
+    let mut other;
+    let list: &mut [Item] = if path == day_path {
+        items
+    } else {
+        other = peek_items(&path)?.unwrap_or_default();
+        &mut other
+    };
+
+    let Some(item) = list.iter_mut().find(|i| i.text == text) else {
+        return Ok(format!("'{}' is gone, its time was not saved", text));
+    };
+    item.spent = Some(item.spent.unwrap_or(0).saturating_add(minutes));
+    write_items(list, &path, options)?;
+
+    return Ok(format!("Spent {}m on '{}'", minutes, text));
+}
+
+/// Stop the timer when it runs on the just completed item at `index`, with
+/// `stop_timer_on_done`
+fn stop_timer_on_done(
+    config: &config::Config,
+    items: &mut [Item],
+    index: usize,
+    day_path: &Path,
+    timer: &mut Option<(PathBuf, String, std::time::Instant)>,
+) -> Result<Option<String>> {
+    if !config.stop_timer_on_done || !items[index].completed {
+        return Ok(None);
+    }
+
+    return match timer.take_if(|(path, text, _)| path == day_path && *text == items[index].text) {
+        Some(timer) => Ok(Some(stop_timer(
+            items,
+            day_path,
+            timer,
+            &WriteOptions::new(config),
+        )?)),
+        None => Ok(None),
+    };
+}
+
+/// Whether the text of the item, with its tokens, contains `query`, ignoring case
+pub fn item_matches(item: &Item, query: &str) -> bool {
+    return item
+        .body()
+        .to_ascii_lowercase()
+        .contains(&query.to_ascii_lowercase());
+}
+
+/// The text split into spans, with every occurrence of `query` highlighted
+fn highlight_matches(text: &str, query: &str) -> Vec<Span<'static>> {
+    let style = Style::default().add_modifier(Modifier::REVERSED);
+    let lower = text.to_ascii_lowercase();
+    let query = query.to_ascii_lowercase();
+
+    let mut spans = Vec::new();
+    let mut last = 0;
+    for (at, _) in lower.match_indices(&query) {
+        spans.push(Span::raw(text[last..at].to_string()));
+        spans.push(Span::styled(text[at..at + query.len()].to_string(), style));
+        last = at + query.len();
+    }
+    spans.push(Span::raw(text[last..].to_string()));
+
+    return spans;
+}
+
+/// Every parsed field of the item that is set, labeled, for the detail pane
+fn item_details(item: &Item, day: NaiveDate) -> Vec<(&'static str, String)> {
+    let status = match item.status() {
+        'x' => "done",
+        '~' => "cancelled",
+        _ => "pending",
+    };
+    let mut details = vec![("Text", item.text.clone()), ("Status", status.to_string())];
+
+    if item.indent > 0 {
+        details.push(("Level", format!("subtask, level {}", item.indent)));
+    }
+    if let Some(color) = item.color {
+        details.push(("Label", color.to_string().to_lowercase()));
+    }
+    if let Some(every) = item.every {
+        details.push(("Every", format!("{} day(s)", every)));
+    }
+    if let Some(due) = item.due {
+        let time = item.due_time.map(|t| t.format(" %H:%M").to_string());
+        details.push(("Due", format!("{}{}", due, time.unwrap_or_default())));
+    }
+    if let Some(estimate) = item.estimate {
+        details.push((
+            "Estimate",
+            format!("{}h{:02}m", estimate / 60, estimate % 60),
+        ));
+    }
+    if let Some(spent) = item.spent {
+        details.push(("Spent", format!("{}h{:02}m", spent / 60, spent % 60)));
+    }
+    if let Some(created) = item.created {
+        let age = item.age(day).unwrap_or_default();
+        details.push(("Created", format!("{} ({}d ago)", created, age)));
+    }
+    if let Some(done_at) = item.done_at {
+        details.push(("Done at", done_at.format("%Y-%m-%d %H:%M").to_string()));
+    }
+    if let Some(times) = item.carried {
+        details.push(("Carried", format!("{} time(s)", times)));
+    }
+    if let Some(blocked_by) = &item.blocked_by {
+        details.push(("Blocked by", blocked_by.clone()));
+    }
+    if !item.tags().is_empty() {
+        details.push(("Tags", item.tags().join(" ")));
+    }
+    if let Some(url) = item.url() {
+        details.push(("Link", url.to_string()));
+    }
+
+    return details;
+}
+
+/// Render the items of a day as a short text grouped by done and pending
+pub fn summary(day_name: &str, items: &[Item]) -> String {
+    let mut lines = vec![day_name.to_string()];
+
+    for (title, status) in [("Done", 'x'), ("Pending", ' '), ("Cancelled", '~')] {
+        let group = items
+            .iter()
+            .filter(|i| i.status() == status)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>();
+
+        if !group.is_empty() {
+            lines.push(String::new());
+            lines.push(format!("{}:", title));
+            lines.extend(group);
+        }
+    }
+
+    return lines.join("\n");
+}
+
+/// A day as markdown to paste in a report, without the checkboxes and tokens
+pub fn share_markdown(day: NaiveDate, day_name: &str, items: &[Item], done_only: bool) -> String {
+    let mut lines = vec![format!("## {} {}", day.format("%A"), day_name)];
+    let sections = if done_only {
+        &[("Done", 'x')][..]
+    } else {
+        &[("Done", 'x'), ("Pending", ' '), ("Cancelled", '~')][..]
+    };
+
+    // A subtask is only nested under its parent when both land in the same section
+    let mut depths: Vec<usize> = Vec::with_capacity(items.len());
+    for (i, item) in items.iter().enumerate() {
+        let parent = items[..i].iter().rposition(|p| p.indent < item.indent);
+        depths.push(match parent {
+            Some(p) if items[p].status() == item.status() => depths[p] + 1,
+            _ => 0,
+        });
+    }
+
+    for (title, status) in sections {
+        let group = items
+            .iter()
+            .zip(&depths)
+            .filter(|(i, _)| i.status() == *status)
+            .map(|(i, depth)| format!("{}- {}", "  ".repeat(*depth), i.text))
+            .collect::<Vec<_>>();
+
+        if !group.is_empty() {
+            lines.push(String::new());
+            lines.push(format!("### {}", title));
+            lines.push(String::new());
+            lines.extend(group);
+        }
+    }
+
+    return lines.join("\n");
+}
+
+/// Copy text to the system clipboard, falling back to a temporary file
+///
+/// Returns a message describing where the text ended up.
+pub fn copy_to_clipboard(text: &str, name: &str) -> Result<String> {
+    let copied = arboard::Clipboard::new().and_then(|mut c| c.set_text(text));
+
+    if copied.is_ok() {
+        return Ok("Copied to clipboard".to_string());
+    }
+
+    let path = std::env::temp_dir().join(format!("todo-{}.md", name));
+    fs::write(&path, text)?;
+
+    return Ok(format!(
+        "No clipboard available, saved to {}",
+        path.display()
+    ));
+}
+
+/// Resolve a `--date` argument to a day offset relative to today
+///
+/// Accepts `today`, `yesterday`, `tomorrow`, or a date in either the configured
+/// format or `%Y-%m-%d`.
+pub fn date_offset(config: &config::Config, s: &str) -> Result<i64> {
+    let offset = match s {
+        "today" => 0,
+        "yesterday" => -1,
+        "tomorrow" => 1,
+        _ => {
+            let day = NaiveDate::parse_from_str(s, &config.date_format)
+                .or_else(|_| NaiveDate::parse_from_str(s, "%Y-%m-%d"))
+                .with_context(|| format!("Invalid date '{}'", s))?;
+
+            (day - today(config)).num_days()
+        }
+    };
+
+    return Ok(offset);
+}
+
+/// Resolve an optional `--date` argument, defaulting to today
+pub fn date_arg_offset(config: &config::Config, date_arg: Option<&str>) -> Result<i64> {
+    return match date_arg {
+        Some(s) => date_offset(config, s),
+        None => Ok(0),
+    };
+}
+
+/// State file remembering the day shown when the TUI was closed
+fn last_day_file(config: &config::Config) -> PathBuf {
+    return Path::new(&config.path).join(".last-day");
+}
+
+/// Offset of the day saved in `.last-day`, if there is a readable one
+fn read_last_day(config: &config::Config) -> Option<i64> {
+    let text = fs::read_to_string(last_day_file(config)).ok()?;
+    let day = NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d").ok()?;
+
+    return Some((day - today(config)).num_days());
+}
+
+/// Whether the file is one of the lists without a date, the inbox or the someday list
+pub fn is_undated(config: &config::Config, path: &Path) -> bool {
+    return path == config.inbox_file() || path == config.someday_file();
+}
+
+/// Whether the TUI may change the day at `offset`, stored at `path`
+///
+/// Days before today follow `edit_past`; `confirmed` is whether the change was
+/// asked for twice, which `"confirm"` needs.
+fn can_edit_day(config: &config::Config, offset: i64, path: &Path, confirmed: bool) -> bool {
+    if offset >= 0 || is_undated(config, path) {
+        return true;
+    }
+
+    return match config.edit_past {
+        config::EditPast::Allow => true,
+        config::EditPast::Confirm => confirmed,
+        config::EditPast::Deny => false,
+    };
+}
+
+/// Sidecar file whose presence marks a day as a rest day
+pub fn rest_file<P>(day_path: P) -> PathBuf
+where
+    P: AsRef<Path>,
+{
+    return day_path.as_ref().with_extension("rest");
+}
+
+/// Sidecar file with free-form journal notes about a day
+pub fn note_file<P>(day_path: P) -> PathBuf
+where
+    P: AsRef<Path>,
+{
+    return day_path.as_ref().with_extension("note");
+}
+
+/// The first non-empty line of the notes of a day, read without loading the rest
+pub fn note_preview<P>(day_path: P) -> Option<String>
+where
+    P: AsRef<Path>,
+{
+    let file = fs::File::open(note_file(day_path)).ok()?;
+
+    return std::io::BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .map(|line| line.trim().to_string())
+        .find(|line| !line.is_empty());
+}
+
+/// Sidecar file keeping the completed tasks deleted from a day, with
+/// `retain_completed_history`
+pub fn history_file<P>(day_path: P) -> PathBuf
+where
+    P: AsRef<Path>,
+{
+    return day_path.as_ref().with_extension("history");
+}
+
+/// Append the completed ones of the `removed` tasks to the history of the day
+fn retain_history<P>(config: &config::Config, day_path: P, removed: &[Item]) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let completed = removed.iter().filter(|i| i.completed).collect::<Vec<_>>();
+    if !config.retain_completed_history || completed.is_empty() {
+        return Ok(());
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_file(day_path))?;
+    for item in completed {
+        writeln!(file, "{}", item)?;
+    }
+
+    return Ok(());
+}
+
+pub fn is_rest_day<P>(day_path: P) -> bool
+where
+    P: AsRef<Path>,
+{
+    return rest_file(day_path).exists();
+}
+
+pub fn day_file(config: &config::Config, day_name: &str) -> PathBuf {
+    if config.mode == config::Mode::Single {
+        return Path::new(&config.path).join("todo.md");
+    }
+
+    return Path::new(&config.path).join(format!("{}.md", day_name));
+}
+
+/// Every markdown file in the tasks directory and its subdirectories, with its name
+/// relative to the directory and without the extension
+pub fn markdown_files(config: &config::Config) -> Result<Vec<(String, PathBuf)>> {
+    let root = Path::new(&config.path);
+    let mut dirs = vec![root.to_path_buf()];
+    let mut files = Vec::new();
+
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            if path.extension().is_none_or(|e| e != "md") {
+                continue;
+            }
+
+            let name = path.strip_prefix(root)?.with_extension("");
+            files.push((name.to_string_lossy().to_string(), path));
+        }
+    }
+    files.sort();
+
+    return Ok(files);
+}
+
+/// Every day file in the tasks directory, oldest first, with the day it is for
+///
+/// Subdirectories are searched too, for a `date_format` like `%Y/%m/%d`.
+pub fn day_files(config: &config::Config) -> Result<Vec<(NaiveDate, PathBuf)>> {
+    let mut files = markdown_files(config)?
+        .into_iter()
+        .filter_map(|(name, path)| {
+            let day = NaiveDate::parse_from_str(&name, &config.date_format).ok()?;
+            Some((day, path))
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+
+    return Ok(files);
+}
+
+/// The day files of `day_files` left after `history_scan_limit`, or `limit` when
+/// given, which drops the days more than that many days in the past
+pub fn recent_day_files(
+    config: &config::Config,
+    limit: Option<u32>,
+) -> Result<Vec<(NaiveDate, PathBuf)>> {
+    let mut files = day_files(config)?;
+    if let Some(days) = limit.or(config.history_scan_limit) {
+        let first = today(config) - Days::new(u64::from(days));
+        files.retain(|(day, _)| *day >= first);
+    }
+
+    return Ok(files);
+}
+
+/// The goals shown next to the day at `offset`, when `goals_format` is set and the
+/// goals file exists
+fn read_goals(config: &config::Config, offset: i64) -> Result<Option<(String, Vec<Item>)>> {
+    let Some(format) = &config.goals_format else {
+        return Ok(None);
+    };
+
+    let name = date(config, offset, format);
+    let goals = peek_items(day_file(config, &name))?;
+
+    return Ok(goals.map(|items| (name, items)));
+}
+
+/// Log to `todo.log` in the tasks directory with `--verbose` or `RUST_LOG`, since
+/// the TUI owns the terminal
+fn init_logging(config: &config::Config, verbose: bool) -> Result<()> {
+    if !verbose && std::env::var_os("RUST_LOG").is_none() {
+        return Ok(());
+    }
+
+    let path = Path::new(&config.path).join("todo.log");
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open the log file {}", path.display()))?;
+
+    let mut builder = env_logger::Builder::from_default_env();
+    if verbose {
+        builder.filter_level(log::LevelFilter::Debug);
+    }
+    builder
+        .target(env_logger::Target::Pipe(Box::new(file)))
+        .init();
+
+    return Ok(());
+}
+
+fn main() -> Result<()> {
+    let args = args::Args::parse();
+    let mut config = match args.config {
+        Some(path) => config::Config::from_file(&path)?,
+        None => config::Config::parse()?,
+    };
+
+    if args.high_contrast {
+        config.high_contrast = true;
+    }
+
+    if config.day_start_hour > 23 {
+        bail!("`day_start_hour` must be between 0 and 23");
+    }
+    // The TUI always owns a terminal, so only `NO_COLOR` makes it plain
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    config.plain = no_color || (args.subcmd.is_some() && !stdout().is_terminal());
+
+    fs::create_dir_all(&config.path)?;
+    init_logging(&config, args.verbose)?;
+    log::debug!("Loaded config {:?}", config);
+
+    match args.subcmd {
+        Some(args::SubCommand::Status { weighted, strict }) => status(config, weighted, strict),
+        Some(args::SubCommand::Details {
+            page,
+            limit,
+            reverse,
+        }) => details(config, page, limit, reverse),
+        Some(args::SubCommand::Agenda { scan_limit }) => agenda(config, scan_limit),
+        Some(args::SubCommand::Week) => week(config),
+        Some(args::SubCommand::Tags {
+            days,
+            tag,
+            rollup,
+            include_history,
+        }) => tags(config, days, tag, rollup, include_history),
+        Some(args::SubCommand::WeekdayStats { weeks }) => weekday_stats(config, weeks),
+        Some(args::SubCommand::Pull { from, yes }) => pull(config, from, yes),
+        Some(args::SubCommand::Prune { empty, yes }) => prune(config, empty, yes),
+        Some(args::SubCommand::Path { date }) => print_path(config, date),
+        Some(args::SubCommand::Share {
+            date,
+            done_only,
+            copy,
+        }) => share(config, date, done_only, copy),
+        Some(args::SubCommand::Diff { from, to }) => diff(config, from, to),
+        Some(args::SubCommand::Plan { days }) => plan(config, days),
+        Some(args::SubCommand::Heatmap { habit, year }) => heatmap(config, habit, year),
+        Some(args::SubCommand::New { since }) => new(config, since),
+        Some(args::SubCommand::Export { format }) => export(config, format),
+        Some(args::SubCommand::Ics {
+            from,
+            to,
+            scan_limit,
+        }) => ics(config, from, to, scan_limit),
+        Some(args::SubCommand::Add { text, stdin, date }) => add(config, text, stdin, date),
+        Some(args::SubCommand::Done { text, on }) => done(config, text, on),
+        Some(args::SubCommand::Edit { index, text, date }) => edit(config, index, text, date),
+        Some(args::SubCommand::Replace { from, to, date }) => replace(config, from, to, date),
+        Some(args::SubCommand::Inbox { action }) => inbox(config, action),
+        Some(args::SubCommand::Someday { action }) => someday(config, action),
+        Some(args::SubCommand::Fix { date }) => fix(config, date),
+        Some(args::SubCommand::Check) => check(config),
+        None => tui(config),
+    }
+}
+
+fn status(config: config::Config, weighted: bool, strict: bool) -> Result<()> {
+    let day_offset = 0;
+    let day_name = date(&config, day_offset, &config.date_format);
+    let day_path = day_file(&config, &day_name);
+
+    let items = read_items(&day_path, &config.habits, &WriteOptions::new(&config))?;
+
+    let (completed, total) = progress(&config, &items);
+
+    if !weighted {
+        println!("{}/{}", completed, total);
+    } else {
+        // Tasks without an estimate weigh one minute each
+        let counted = items
+            .iter()
+            .filter(|i| config.count_cancelled || !i.cancelled)
+            .collect::<Vec<_>>();
+        let weight = |i: &&Item| i.estimate.unwrap_or(1);
+        let done_weight = counted
+            .iter()
+            .filter(|i| i.completed)
+            .map(weight)
+            .sum::<u32>();
+        let total_weight = counted.iter().map(weight).sum::<u32>();
+
+        let percent = |part: f64, whole: f64| {
+            if whole > 0.0 {
+                part * 100.0 / whole
+            } else {
+                0.0
+            }
+        };
+        println!(
+            "{}/{} ({:.0}%), weighted {:.0}%",
+            completed,
+            total,
+            percent(completed as f64, total as f64),
+            percent(f64::from(done_weight), f64::from(total_weight))
+        );
+    }
+
+    if strict {
+        let unmet = unmet_required(&config, &items);
+        if unmet.is_empty() {
+            println!("All required tasks done");
+        } else {
+            println!("Incomplete, required tasks not done: {}", unmet.join(", "));
+        }
+    }
+
+    return Ok(());
+}
+
+fn details(config: config::Config, page: bool, limit: Option<usize>, reverse: bool) -> Result<()> {
+    let day_offset = 0;
+    let day_name = date(&config, day_offset, &config.date_format);
+    let day_path = day_file(&config, &day_name);
+
+    let mut items = read_items(&day_path, &config.habits, &WriteOptions::new(&config))?;
+    if reverse {
+        items.reverse();
+    }
+    items.truncate(limit.unwrap_or(items.len()));
+
+    let text: String = items.iter().map(|i| format!("{}\n", i)).collect();
+    if page {
+        return page_text(&text);
+    }
+
+    print!("{}", text);
+
+    return Ok(());
+}
+
+/// Take over the terminal for the TUI
+fn enter_tui() -> Result<()> {
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?;
+    stdout().execute(EnableMouseCapture)?;
+    stdout().execute(EnableFocusChange)?;
+    stdout().execute(EnableBracketedPaste)?;
+
+    return Ok(());
+}
+
+/// Give the terminal back as it was before `enter_tui`
+fn leave_tui() -> Result<()> {
+    disable_raw_mode()?;
+    stdout().execute(LeaveAlternateScreen)?;
+    stdout().execute(DisableMouseCapture)?;
+    stdout().execute(DisableFocusChange)?;
+    stdout().execute(DisableBracketedPaste)?;
+
+    return Ok(());
+}
+
+/// Open the day in `editor` and read it back as the editor left it
+///
+/// `leave` hands the terminal to the editor and `enter` takes it back for the
+/// TUI, whatever happens to the editor. The file is reloaded either way, and the
+/// message says how the editor exited.
+fn edit_day(
+    config: &config::Config,
+    path: &Path,
+    habits: &[String],
+    editor: &str,
+    leave: impl FnOnce() -> Result<()>,
+    enter: impl FnOnce() -> Result<()>,
+) -> Result<(Vec<Item>, String)> {
+    // Through the shell, so an `$EDITOR` like `code --wait` works
+    let status = leave().and_then(|_| {
+        return Ok(std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(path)
+            .status()?);
+    });
+
+    enter()?;
+
+    return reload_day(config, path, habits, editor_message(editor, &status));
+}
+
+/// Say how the editor exited, for the status line after reloading
+fn editor_message(editor: &str, status: &Result<std::process::ExitStatus>) -> String {
+    return match status {
+        Ok(status) if status.success() => "Reloaded after editing".to_string(),
+        Ok(status) => match status.code() {
+            Some(code) => format!("{} exited with status {}; reloaded the file", editor, code),
+            None => format!("{} was killed; reloaded the file", editor),
+        },
+        Err(err) => format!("Could not run {}: {}; reloaded the file", editor, err),
+    };
+}
+
+/// Read the day again after it may have changed on disk
+///
+/// `note` is the message to show, unless reading the file turned up a warning.
+fn reload_day(
+    config: &config::Config,
+    path: &Path,
+    habits: &[String],
+    note: String,
+) -> Result<(Vec<Item>, String)> {
+    let (items, warning) = read_day(config, path, habits)?;
+    let message = warning.or(size_warning(config, &items)).unwrap_or(note);
+
+    return Ok((items, message));
+}
+
+/// Show text through `$PAGER`, or print it when no pager can be started
+fn page_text(text: &str) -> Result<()> {
+    let pager = std::env::var("PAGER").unwrap_or("less".to_string());
+
+    let child = std::process::Command::new(&pager)
+        .stdin(std::process::Stdio::piped())
+        .spawn();
+
+    let mut child = match child {
+        Ok(child) => child,
+        Err(_) => {
+            print!("{}", text);
+            return Ok(());
+        }
+    };
+
+    if let Some(mut pipe) = child.stdin.take() {
+        // The pager may quit before reading everything, which is fine
+        let _ = pipe.write_all(text.as_bytes());
+    }
+    child
+        .wait()
+        .with_context(|| format!("Failed to wait for pager {}", pager))?;
+
+    return Ok(());
+}
+
+/// The open tasks due on `day` from the files of the other days, with the name of
+/// the day they are in; past days go back as far as `recent_day_files` allows
+fn due_elsewhere(
+    config: &config::Config,
+    day: NaiveDate,
+    limit: Option<u32>,
+) -> Result<Vec<(String, Item)>> {
+    let mut due = Vec::new();
+
+    for (other, path) in recent_day_files(config, limit)? {
+        if other == day {
+            continue;
+        }
+
+        for item in peek_items(&path)?.unwrap_or_default() {
+            if item.is_open() && item.due == Some(day) {
+                due.push((other.format(&config.date_format).to_string(), item));
+            }
+        }
+    }
+
+    return Ok(due);
+}
+
+fn agenda(config: config::Config, scan_limit: Option<u32>) -> Result<()> {
+    let day = today(&config);
+    let day_name = date(&config, 0, &config.date_format);
+    let day_path = day_file(&config, &day_name);
+
+    let mut agenda = peek_items(&day_path)?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|item| (item, None))
+        .collect::<Vec<_>>();
+    agenda.extend(
+        due_elsewhere(&config, day, scan_limit)?
+            .into_iter()
+            .map(|(from, item)| (item, Some(from))),
+    );
+
+    // Timed tasks first, soonest first; the others keep their order
+    agenda.sort_by_key(|(item, _)| (item.due_time.is_none(), item.due_time));
+
+    let now = Local::now().naive_local();
+    for (item, from) in agenda {
+        let mut line = item.to_string();
+        if let Some(from) = from {
+            line.push_str(&format!(
+                " {}",
+                styled(&config, format!("(from {})", from).dark_grey())
+            ));
+        }
+        if item.is_open() && item.due_at().is_some_and(|due| due < now) {
+            line.push_str(&format!(" {}", styled(&config, "(overdue)".red())));
+        }
+
+        println!("{}", line);
+    }
+
+    return Ok(());
+}
+
+fn week(config: config::Config) -> Result<()> {
+    let monday = -i64::from(today(&config).weekday().num_days_from_monday());
+    let mut cache = cache::StatsCache::load(&config);
+
+    for offset in monday..monday + 7 {
+        let day_name = date(&config, offset, &config.date_format);
+        let weekday = offset_day(&config, offset).format("%a");
+
+        let day_path = day_file(&config, &day_name);
+
+        let (mark, progress) = match cache.progress(&config, &day_path)? {
+            _ if is_rest_day(&day_path) => ("~".blue(), "rest".to_string()),
+            Some((completed, total)) => {
+                let progress = format!("{}/{}", completed, total);
+
+                if total > 0 && completed == total {
+                    ("✓".green(), progress)
+                } else {
+                    ("·".yellow(), progress)
+                }
+            }
+            None => ("·".dark_grey(), "-".to_string()),
+        };
+
+        let line = format!(
+            "{} {:<12} {} {:>5}",
+            weekday,
+            day_name,
+            styled(&config, mark),
+            progress
+        );
+        match offset {
+            0 if config.plain => println!("{} (today)", line),
+            0 => println!("{}", line.bold()),
+            _ => println!("{}", line),
+        }
+    }
+
+    cache.save()?;
+
+    return Ok(());
+}
+
+fn tags(
+    config: config::Config,
+    days: u32,
+    filter: Option<String>,
+    rollup: bool,
+    deleted: bool,
+) -> Result<()> {
+    let mut counts: HashMap<String, (usize, usize)> = HashMap::new();
+
+    for (_, _, items) in read_history(&config, days, deleted)? {
+        for item in items.iter() {
+            // A task tagged `+work/a` and `+work/b` counts once for `+work`
+            let seen = item
+                .tags()
+                .into_iter()
+                .flat_map(|tag| match rollup {
+                    true => item::tag_levels(tag),
+                    false => vec![tag],
+                })
+                .filter(|tag| filter.as_deref().is_none_or(|f| item::is_subtag(tag, f)))
+                .collect::<HashSet<_>>();
+
+            for tag in seen {
+                let (total, completed) = counts.entry(tag.to_string()).or_default();
+                *total += 1;
+                if item.completed {
+                    *completed += 1;
+                }
+            }
+        }
+    }
+
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    if rollup {
+        // Keep each parent right above its subtags
+        counts.sort();
+    } else {
+        counts.sort_by(|(a, (a_total, _)), (b, (b_total, _))| b_total.cmp(a_total).then(a.cmp(b)));
+    }
+
+    for (tag, (total, completed)) in counts {
+        println!("{:<20} {}/{}", tag, completed, total);
+    }
+
+    return Ok(());
+}
+
+fn weekday_stats(config: config::Config, weeks: u32) -> Result<()> {
+    // Sum of the completion ratios and number of days, indexed from Monday
+    let mut buckets = [(0.0, 0); 7];
+    let mut cache = cache::StatsCache::load(&config);
+
+    let days = i64::from(weeks) * 7;
+    if today(&config)
+        .checked_sub_days(Days::new(days.unsigned_abs()))
+        .is_none()
+    {
+        bail!("{} weeks go back further than the earliest date", weeks);
+    }
+
+    for offset in (1 - days)..=0 {
+        let day_path = day_file(&config, &date(&config, offset, &config.date_format));
+        if is_rest_day(&day_path) {
+            continue;
+        }
+
+        let Some((completed, total)) = cache.progress(&config, &day_path)? else {
+            continue;
+        };
+        if total == 0 {
+            continue;
+        }
+
+        let bucket =
+            &mut buckets[offset_day(&config, offset).weekday().num_days_from_monday() as usize];
+        bucket.0 += completed as f64 / total as f64;
+        bucket.1 += 1;
+    }
+    cache.save()?;
+
+    let mut weekday = Weekday::Mon;
+    for (sum, days) in buckets {
+        match days {
+            0 => println!("{}     -", weekday),
+            _ => println!(
+                "{} {:>4.0}% ({} day(s))",
+                weekday,
+                sum * 100.0 / days as f64,
+                days
+            ),
+        }
+        weekday = weekday.succ();
+    }
+
+    return Ok(());
+}
+
+fn pull(config: config::Config, from: String, yes: bool) -> Result<()> {
+    if config.mode == config::Mode::Single {
+        bail!("There are no days to pull from with the single mode");
+    }
+
+    let from_offset = date_offset(&config, &from)?;
+    if from_offset == 0 {
+        bail!("The tasks of today are already there");
+    }
+
+    let from_name = date(&config, from_offset, &config.date_format);
+    let from_path = day_file(&config, &from_name);
+    let Some(mut items) = peek_items(&from_path)? else {
+        bail!("No task file for {}", from_name);
+    };
+    for item in items.iter_mut() {
+        item.is_habit = config.habits.contains(&item.text);
+    }
+
+    let mut pulled = take_pending(&mut items);
+    if pulled.is_empty() {
+        println!("Nothing left to do on {}", from_name);
+        return Ok(());
+    }
+
+    if !yes {
+        for item in &pulled {
+            println!("{}", item);
+        }
+        print!(
+            "Move {} task(s) from {} to today? [y/N] ",
+            pulled.len(),
+            from_name
+        );
+        stdout().flush()?;
+
+        let mut answer = String::new();
+        stdin().lock().read_line(&mut answer)?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!("Nothing moved");
+            return Ok(());
+        }
+    }
+
+    let count = pulled.len();
+    if from_offset < 0 {
+        mark_carried(&config, &mut pulled);
+    }
+    let (day_name, warning) = move_to_day(&config, 0, pulled)?;
+    if let Some(warning) = warning {
+        eprintln!("Warning: {}", warning);
+    }
+    write_items(&items, &from_path, &WriteOptions::new(&config))?;
+
+    println!("Moved {} task(s) from {} to {}", count, from_name, day_name);
+
+    return Ok(());
+}
+
+fn prune(config: config::Config, empty: bool, yes: bool) -> Result<()> {
+    if !empty {
+        bail!("Nothing to prune; pass --empty");
+    }
+
+    // Anything but whitespace is content someone may want, even if it is not a task
+    let mut empties = Vec::new();
+    for (_, path) in day_files(&config)? {
+        if fs::read_to_string(&path)?.trim().is_empty() {
+            empties.push(path);
+        }
+    }
+
+    if empties.is_empty() {
+        println!("No empty day files");
+        return Ok(());
+    }
+
+    if !yes {
+        for path in &empties {
+            println!("{}", path.display());
+        }
+        print!("Delete {} empty day file(s)? [y/N] ", empties.len());
+        stdout().flush()?;
+
+        let mut answer = String::new();
+        stdin().lock().read_line(&mut answer)?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!("Nothing deleted");
+            return Ok(());
+        }
+    }
+
+    for path in &empties {
+        fs::remove_file(path)?;
+        println!("Removed {}", path.display());
+    }
+
+    return Ok(());
+}
+
+fn print_path(config: config::Config, date_arg: Option<String>) -> Result<()> {
+    let day_offset = date_arg_offset(&config, date_arg.as_deref())?;
+    let day_name = date(&config, day_offset, &config.date_format);
+
+    println!(
+        "{}",
+        std::path::absolute(day_file(&config, &day_name))?.display()
+    );
+
+    return Ok(());
+}
+
+fn share(
+    config: config::Config,
+    date_arg: Option<String>,
+    done_only: bool,
+    copy: bool,
+) -> Result<()> {
+    let day_offset = date_arg_offset(&config, date_arg.as_deref())?;
+    let day_name = date(&config, day_offset, &config.date_format);
+    let day_path = day_file(&config, &day_name);
+
+    let Some(items) = peek_items(&day_path)? else {
+        bail!("No task file for {}", day_name);
+    };
+
+    let text = share_markdown(
+        offset_day(&config, day_offset),
+        &day_name,
+        &items,
+        done_only,
+    );
+    if copy {
+        println!("{}", copy_to_clipboard(&text, &day_name)?);
+    } else {
+        println!("{}", text);
+    }
+
+    return Ok(());
+}
+
+/// Tasks are matched by text; a repeated text is matched in order
+fn diff(config: config::Config, from: String, to: String) -> Result<()> {
+    let read_day = |day: &str| -> Result<Vec<Item>> {
+        let day_name = date(&config, date_offset(&config, day)?, &config.date_format);
+        return match peek_items(day_file(&config, &day_name))? {
+            Some(items) => Ok(items),
+            None => bail!("There is no file for {}", day_name),
+        };
+    };
+    let mut before = read_day(&from)?;
+    let after = read_day(&to)?;
+
+    for item in after {
+        match before.iter().position(|old| old.text == item.text) {
+            Some(i) => {
+                let old = before.remove(i);
+                if old.status() != item.status() {
+                    let line = format!("~ [{}] -> [{}] {}", old.status(), item.status(), item.text);
+                    println!("{}", styled(&config, line.yellow()));
+                }
+            }
+            None => println!("{}", styled(&config, format!("+ {}", item.text).green())),
+        }
+    }
+    for item in before {
+        println!("{}", styled(&config, format!("- {}", item.text).red()));
+    }
+
+    return Ok(());
+}
+
+fn plan(config: config::Config, days: u32) -> Result<()> {
+    if config.mode == config::Mode::Single {
+        bail!("There are no days to plan with the single mode");
+    }
+
+    let mut offset = 0;
+    for _ in 0..days {
+        offset = step_offset(&config, offset, 1);
+        let day_name = date(&config, offset, &config.date_format);
+        let day_path = day_file(&config, &day_name);
+
+        if day_path.exists() {
+            println!("{} already exists", day_name);
+            continue;
+        }
+
+        let items = read_items(&day_path, &config.habits, &WriteOptions::new(&config))?;
+        println!("{} created with {} task(s)", day_name, items.len());
+    }
+
+    return Ok(());
+}
+
+/// One column per week and one row per weekday, like a contribution graph
+fn heatmap(config: config::Config, habit: String, year: Option<i32>) -> Result<()> {
+    let year = year.unwrap_or(today(&config).year());
+    let (Some(first), Some(last)) = (
+        NaiveDate::from_ymd_opt(year, 1, 1),
+        NaiveDate::from_ymd_opt(year, 12, 31),
+    ) else {
+        bail!("Invalid year {}", year);
+    };
+
+    let start = first - Days::new(u64::from(first.weekday().num_days_from_monday()));
+    let week_of = |day: NaiveDate| usize::try_from((day - start).num_days() / 7).unwrap_or(0);
+    let mut grid = vec![vec![" ".to_string(); week_of(last) + 1]; 7];
+    let mut months = " ".repeat(week_of(last) + 4);
+    let (mut done, mut tracked) = (0, 0);
+
+    for day in first.iter_days().take_while(|&day| day <= last) {
+        if day.day() == 1 {
+            let at = week_of(day);
+            months.replace_range(at..at + 3, &day.format("%b").to_string());
+        }
+
+        let day_path = day_file(&config, &day.format(&config.date_format).to_string());
+        let found = peek_items(&day_path)?.and_then(|items| {
+            items
+                .into_iter()
+                .find(|i| i.text.eq_ignore_ascii_case(&habit))
+        });
+
+        let cell = match found {
+            _ if is_rest_day(&day_path) => ('~', " ".on_blue()),
+            Some(item) if item.completed => {
+                tracked += 1;
+                done += 1;
+                ('#', " ".on_green())
+            }
+            Some(_) => {
+                tracked += 1;
+                ('.', " ".on_dark_grey())
+            }
+            None => continue,
+        };
+
+        grid[day.weekday().num_days_from_monday() as usize][week_of(day)] = match config.plain {
+            true => cell.0.to_string(),
+            false => cell.1.to_string(),
+        };
+    }
+
+    println!("    {}", months.trim_end());
+    for (row, weekday) in grid
+        .iter()
+        .zip(["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"])
+    {
+        println!("{} {}", weekday, row.concat());
+    }
+    println!(
+        "{} done on {} of {} day(s) in {}",
+        habit, done, tracked, year
+    );
+
+    return Ok(());
+}
+
+/// A task carried over to later days is listed once, on the last day it is in
+fn new(config: config::Config, since: String) -> Result<()> {
+    let since = offset_day(&config, date_offset(&config, &since)?);
+
+    let mut added: Vec<(NaiveDate, NaiveDate, Item)> = Vec::new();
+    for (day, path) in day_files(&config)?
+        .into_iter()
+        .filter(|(day, _)| *day >= since)
+    {
+        for item in peek_items(&path)?.unwrap_or_default() {
+            let Some(created) = item.created.filter(|&created| created >= since) else {
+                continue;
+            };
+
+            added.retain(|(c, _, i)| *c != created || i.text != item.text);
+            added.push((created, day, item));
+        }
+    }
+    added.sort_by_key(|(created, _, _)| *created);
+
+    for (created, day, item) in added {
+        println!("{} [{}] {} (on {})", created, item.status(), item.text, day);
+    }
+
+    return Ok(());
+}
+
+/// Stream every task as it reads each day file, so that years of history take
+/// constant memory
+fn export(config: config::Config, format: args::ExportFormat) -> Result<()> {
+    let mut out = std::io::BufWriter::new(stdout().lock());
+
+    for (day, path) in day_files(&config)? {
+        let Some(items) = peek_items(&path)? else {
+            continue;
+        };
+
+        for item in items {
+            let line = match format {
+                args::ExportFormat::Jsonl => serde_json::json!({
+                    "date": day.to_string(),
+                    "text": item.text,
+                    "completed": item.completed,
+                    "cancelled": item.cancelled,
+                    "indent": item.indent,
+                    "tags": item.tags(),
+                    "label": item.color.map(|color| color.to_string().to_lowercase()),
+                    "every": item.every,
+                    "due": item.due.map(|due| due.to_string()),
+                    "due_time": item.due_time.map(|time| time.format("%H:%M").to_string()),
+                    "estimate": item.estimate,
+                    "spent": item.spent,
+                    "created": item.created.map(|created| created.to_string()),
+                    "blocked_by": item.blocked_by,
+                })
+                .to_string(),
+            };
+
+            match writeln!(out, "{}", line) {
+                // The reader, like `head`, has seen enough
+                Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => return Ok(()),
+                result => result?,
+            }
+        }
+    }
+
+    match out.flush() {
+        Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => return Ok(()),
+        result => result?,
+    }
+
+    return Ok(());
+}
+
+/// Escape a text value of an iCalendar property
+fn ics_escape(text: &str) -> String {
+    return text
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n");
+}
+
+/// Fold an iCalendar content line at 75 bytes, as the format requires
+fn ics_fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+
+    return folded;
+}
+
+fn ics(
+    config: config::Config,
+    from: Option<String>,
+    to: Option<String>,
+    scan_limit: Option<u32>,
+) -> Result<()> {
+    let bound = |day: Option<String>| -> Result<Option<NaiveDate>> {
+        return match day {
+            Some(day) => Ok(Some(offset_day(&config, date_offset(&config, &day)?))),
+            None => Ok(None),
+        };
+    };
+    let (first, last) = (bound(from)?, bound(to)?);
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ");
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//todo-tui//EN".to_string(),
+    ];
+    for (day, path) in recent_day_files(&config, scan_limit)? {
+        for (index, item) in peek_items(&path)?.unwrap_or_default().iter().enumerate() {
+            let Some(due) = item.due else {
+                continue;
+            };
+            if first.is_some_and(|first| due < first) || last.is_some_and(|last| due > last) {
+                continue;
+            }
+
+            lines.push("BEGIN:VTODO".to_string());
+            // The day file and position keep the same task on the same entry between exports
+            lines.push(format!(
+                "UID:{}-{}@todo-tui",
+                day.format("%Y%m%d"),
+                index + 1
+            ));
+            lines.push(format!("DTSTAMP:{}", stamp));
+            lines.push(format!("SUMMARY:{}", ics_escape(&item.text)));
+            lines.push(match item.due_time {
+                Some(time) => format!("DUE:{}T{}00", due.format("%Y%m%d"), time.format("%H%M")),
+                None => format!("DUE;VALUE=DATE:{}", due.format("%Y%m%d")),
+            });
+            lines.push(match item.status() {
+                'x' => "STATUS:COMPLETED".to_string(),
+                '~' => "STATUS:CANCELLED".to_string(),
+                _ => "STATUS:NEEDS-ACTION".to_string(),
+            });
+            if item.completed {
+                lines.push("PERCENT-COMPLETE:100".to_string());
+            }
+            if let Some(done_at) = item.done_at {
+                lines.push(format!("COMPLETED:{}", done_at.format("%Y%m%dT%H%M00Z")));
+            }
+            let categories = item
+                .tags()
+                .iter()
+                .map(|tag| ics_escape(&tag[1..]))
+                .collect::<Vec<_>>();
+            if !categories.is_empty() {
+                lines.push(format!("CATEGORIES:{}", categories.join(",")));
+            }
+            lines.push("END:VTODO".to_string());
+        }
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut out = stdout().lock();
+    for line in lines {
+        match out.write_all(ics_fold(&line).as_bytes()) {
+            Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => return Ok(()),
+            result => result?,
+        }
+    }
+
+    return Ok(());
+}
+
+fn add(
+    config: config::Config,
+    text: Option<String>,
+    from_stdin: bool,
+    date_arg: Option<String>,
+) -> Result<()> {
+    let options = WriteOptions::new(&config);
+    let day_offset = date_arg_offset(&config, date_arg.as_deref())?;
+    let day_name = date(&config, day_offset, &config.date_format);
+    let day_path = day_file(&config, &day_name);
+
+    let lines = match (text, from_stdin) {
+        (_, true) => stdin().lock().lines().collect::<Result<Vec<_>, _>>()?,
+        (Some(text), false) => vec![text],
+        (None, false) => bail!("Nothing to add; pass the task text or --stdin"),
+    };
+
+    let mut items = read_items(&day_path, &config.habits, &options)?;
+    let count = items.len();
+
+    let added = lines
+        .iter()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let line = resolve_due(line, offset_day(&config, day_offset));
+            let mut item = line.parse().unwrap_or_else(|_| Item::new(line));
+            if config.track_created && item.created.is_none() {
+                item.created = Some(today(&config));
+            }
+
+            item
+        });
+    for item in added.collect::<Vec<_>>() {
+        push_item(&mut items, item);
+    }
+
+    write_items(&items, &day_path, &options)?;
+
+    println!("Added {} task(s) to {}", items.len() - count, day_name);
+
+    return Ok(());
+}
+
+fn done(config: config::Config, text: String, on: Option<String>) -> Result<()> {
+    let day_offset = date_arg_offset(&config, on.as_deref())?;
+    let day_name = date(&config, day_offset, &config.date_format);
+    let day_path = day_file(&config, &day_name);
+
+    let Some(mut items) = peek_items(&day_path)? else {
+        bail!("No task file for {}", day_name);
+    };
+
+    let i = find_item(&items, &text)?;
+    if items[i].completed {
+        println!("Already done: {}", items[i]);
+        return Ok(());
+    }
+
+    if let Some(b) = blocker(&items, i) {
+        bail!("'{}' is blocked by '{}'", items[i].text, items[b].text);
+    }
+
+    items[i].completed = true;
+    items[i].cancelled = false;
+    cascade(&config, &mut items, i);
+    write_items(&items, &day_path, &WriteOptions::new(&config))?;
+    run_on_complete(&config, &items[i]);
+
+    println!("{}", items[i]);
+    if let Some((next, warning)) = schedule_next(&config, day_offset, &items[i])? {
+        if let Some(warning) = warning {
+            eprintln!("Warning: {}", warning);
+        }
+        println!("Scheduled the next occurrence on {}", next);
+    }
+
+    return Ok(());
+}
+
+fn edit(
+    config: config::Config,
+    index: usize,
+    text: String,
+    date_arg: Option<String>,
+) -> Result<()> {
+    let day_offset = date_arg_offset(&config, date_arg.as_deref())?;
+    let day_name = date(&config, day_offset, &config.date_format);
+    let day_path = day_file(&config, &day_name);
+
+    let Some(mut items) = peek_items(&day_path)? else {
+        bail!("No task file for {}", day_name);
+    };
+
+    if index == 0 || index > items.len() {
+        bail!(
+            "Index {} is out of range; {} has {} task(s)",
+            index,
+            day_name,
+            items.len()
+        );
+    }
+
+    items[index - 1].set_text(&text);
+
+    write_items(&items, &day_path, &WriteOptions::new(&config))?;
+
+    println!("{}", items[index - 1]);
+
+    return Ok(());
+}
+
+fn replace(
+    config: config::Config,
+    from: String,
+    to: String,
+    date_arg: Option<String>,
+) -> Result<()> {
+    if from.is_empty() {
+        bail!("The text to replace cannot be empty");
+    }
+
+    let day_offset = date_arg_offset(&config, date_arg.as_deref())?;
+    let day_name = date(&config, day_offset, &config.date_format);
+    let day_path = day_file(&config, &day_name);
+
+    let Some(mut items) = peek_items(&day_path)? else {
+        bail!("No task file for {}", day_name);
+    };
+
+    let mut replacements = 0;
+    for item in items.iter_mut() {
+        replacements += item.text.matches(from.as_str()).count();
+        item.text = item.text.replace(from.as_str(), &to);
+    }
+
+    write_items(&items, &day_path, &WriteOptions::new(&config))?;
+
+    println!("Made {} replacement(s) in {}", replacements, day_name);
+
+    return Ok(());
+}
+
+fn inbox(config: config::Config, action: args::InboxCommand) -> Result<()> {
+    let options = WriteOptions::new(&config);
+    let inbox_path = config.inbox_file();
+    let mut items = read_items(&inbox_path, &[], &options)?;
+
+    match action {
+        args::InboxCommand::Add { text } => {
+            push_item(&mut items, Item::new(text));
+            write_items(&items, &inbox_path, &options)?;
+
+            println!("Added to the inbox ({} task(s))", items.len());
+        }
+        args::InboxCommand::List => {
+            for item in items {
+                println!("{}", item);
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+fn someday(config: config::Config, action: args::SomedayCommand) -> Result<()> {
+    let options = WriteOptions::new(&config);
+    let someday_path = config.someday_file();
+    let mut items = read_items(&someday_path, &[], &options)?;
+
+    match action {
+        args::SomedayCommand::Add { text } => {
+            push_item(&mut items, Item::new(text));
+            write_items(&items, &someday_path, &options)?;
+
+            println!("Added to the someday list ({} task(s))", items.len());
+        }
+        args::SomedayCommand::List => {
+            for item in items {
+                println!("{}", item);
+            }
+        }
+        args::SomedayCommand::Move { text, date } => {
+            let offset = date_arg_offset(&config, date.as_deref())?;
+            let i = find_item(&items, &text)?;
+            let end = subtree_end(&items, i);
+            let moved = remove_items(&mut items, i..end);
+            let (name, warning) = move_to_day(&config, offset, moved)?;
+            if let Some(warning) = warning {
+                eprintln!("Warning: {}", warning);
+            }
+            write_items(&items, &someday_path, &options)?;
+
+            println!("Moved to {}", name);
+        }
+    }
+
+    return Ok(());
+}
+
+fn check(config: config::Config) -> Result<()> {
+    let inbox_path = config.inbox_file();
+    let mut issues = 0;
+    let mut files = 0;
+
+    for (name, path) in markdown_files(&config)? {
+        let known = path == inbox_path
+            || path == config.someday_file()
+            || match config.mode {
+                config::Mode::Daily => {
+                    NaiveDate::parse_from_str(&name, &config.date_format).is_ok()
+                }
+                config::Mode::Single => name == "todo",
+            }
+            // Goals files name a month or a year rather than a full date
+            || config.goals_format.as_ref().is_some_and(|format| {
+                let items = chrono::format::StrftimeItems::new(format);
+                chrono::format::parse(&mut chrono::format::Parsed::new(), &name, items).is_ok()
+            });
+
+        let mut problems = Vec::new();
+        if !known {
+            problems.push("name does not match date_format".to_string());
+        }
+
+        let data = String::from_utf8_lossy(&fs::read(&path)?).to_string();
+        let mut seen: HashMap<String, usize> = HashMap::new();
+
+        for (n, line) in data.lines().enumerate() {
+            let n = n + 1;
+            if line.trim().is_empty() || is_heading(line) {
+                continue;
+            }
+
+            match line.parse::<Item>() {
+                Ok(item) => match seen.get(&item.text) {
+                    Some(first) => problems.push(format!(
+                        "line {}: duplicate of line {}: {}",
+                        n, first, item.text
+                    )),
+                    None => {
+                        seen.insert(item.text, n);
+                    }
+                },
+                Err(_) => problems.push(format!("line {}: not a task: {}", n, line)),
+            }
+        }
+
+        if problems.is_empty() {
+            continue;
+        }
+
+        files += 1;
+        issues += problems.len();
+        println!("{}", styled(&config, path.display().to_string().bold()));
+        for problem in problems {
+            println!("  {}", problem);
+        }
+    }
+
+    if issues == 0 {
+        println!("No issues found");
+    } else {
+        println!("{} issue(s) in {} file(s)", issues, files);
+    }
+
+    return Ok(());
+}
+
+fn fix(config: config::Config, date_arg: Option<String>) -> Result<()> {
+    let day_offset = date_arg_offset(&config, date_arg.as_deref())?;
+    let day_name = date(&config, day_offset, &config.date_format);
+    let day_path = day_file(&config, &day_name);
+
+    if !day_path.exists() {
+        bail!("No task file for {}", day_name);
+    }
+
+    let data = fs::read_to_string(&day_path)?;
+
+    let mut items = Vec::new();
+    let mut normalized = 0;
+    let mut dropped = 0;
+
+    let mut headings = Vec::new();
+
+    for line in data.lines().filter(|line| !line.trim().is_empty()) {
+        if is_heading(line) {
+            headings.push(line.trim_end().to_string());
+            continue;
+        }
+
+        match Item::parse_lenient(line) {
+            Some(mut item) => {
+                if item.line(config.bullet) != line {
+                    normalized += 1;
+                }
+                item.headings = std::mem::take(&mut headings);
+                items.push(item);
+            }
+            None => dropped += 1,
+        }
+    }
+    if let Some(last) = items.last_mut() {
+        last.trailing_headings = headings;
+    }
+
+    write_items(&items, &day_path, &WriteOptions::new(&config))?;
+
+    println!("Normalized {} line(s) in {}", normalized, day_name);
+    if dropped > 0 {
+        println!("Dropped {} line(s) that are not tasks", dropped);
+    }
+
+    return Ok(());
+}
+
+/// What the TUI shows, borrowed from its state for drawing one frame
+struct View<'a> {
+    config: &'a config::Config,
+    items: &'a [Item],
+    items_state: &'a ListState,
+    marked: &'a HashSet<usize>,
+    input_mode: &'a InputMode,
+    input_text: &'a str,
+    prompt_text: &'a str,
+    search: Option<&'a str>,
+    message: &'a str,
+    day_name: &'a str,
+    day_path: &'a Path,
+    day_offset: i64,
+    single: bool,
+    adjacent: &'a (String, String),
+    rest_day: bool,
+    note: Option<&'a str>,
+    goals: Option<&'a (String, Vec<Item>)>,
+    show_details: bool,
+    timer: Option<&'a (PathBuf, String, std::time::Instant)>,
+    sort_view: Option<SortView>,
+    due_today: Option<&'a [(String, Item)]>,
+    /// First item shown in the last frame, kept while it holds the selection
+    list_offset: usize,
+    drag: Option<(usize, usize)>,
+}
+
+/// Where the list landed in the frame, for scrolling and the mouse
+#[derive(Debug, Default)]
+struct ListLayout {
+    /// First item shown while the list fits in one column
+    offset: usize,
+    /// Height of each column while the list is split in columns
+    column_rows: Option<usize>,
+    /// Area of the rows while the list fits in one column
+    inner: Option<Rect>,
+}
+
+/// Draw the whole TUI for `view`
+fn draw<B: Backend>(f: &mut Frame<B>, view: &View) -> ListLayout {
+    let View {
+        config,
+        items,
+        items_state,
+        marked,
+        input_mode,
+        input_text,
+        prompt_text,
+        search,
+        message,
+        day_name,
+        day_path,
+        day_offset,
+        single,
+        adjacent,
+        rest_day,
+        note,
+        goals,
+        show_details,
+        timer,
+        sort_view,
+        due_today,
+        drag,
+        list_offset: _,
+    } = *view;
+
+    // Drop the margin and the help line when the terminal is too small for them
+    let size = f.size();
+    let margin = if size.width > 20 && size.height > 8 {
+        1
+    } else {
+        0
+    };
+    let help_height = if size.height > 12 { 2 } else { 0 };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(margin)
+        .constraints(
+            [
+                Constraint::Min(3),
+                Constraint::Length(help_height),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
+        .split(size);
+
+    // Only the visible items are turned into `ListItem`s, so huge days stay fast
+    let list_item = |(index, i): (usize, &Item)| -> ListItem {
+        let age = i.age(offset_day(config, day_offset)).filter(|&age| age > 0);
+
+        let due_in = i
+            .due_at()
+            .filter(|_| i.is_open())
+            .map(|due| due - Local::now().naive_local());
+
+        let style = match i.color {
+            Some(color) => Style::default().fg(color),
+            None if due_in.is_some_and(|d| d < chrono::Duration::zero()) => {
+                Style::default().fg(Color::Red)
+            }
+            None if i.due_time.is_some()
+                && due_in.is_some_and(|d| d < chrono::Duration::hours(1)) =>
+            {
+                Style::default().fg(Color::Yellow)
+            }
+            None if age >= Some(config.age_alert_days) => Style::default().fg(Color::Red),
+            None if age >= Some(config.age_warn_days) => Style::default().fg(Color::Yellow),
+            None if i.is_habit && config.high_contrast => Style::default().fg(Color::LightCyan),
+            None if i.is_habit => Style::default().fg(Color::Cyan),
+            None if config.high_contrast => Style::default().fg(Color::White),
+            None => Style::default(),
+        };
+
+        let style = match blocker(items, index) {
+            _ if i.cancelled => style.add_modifier(Modifier::DIM | Modifier::CROSSED_OUT),
+            Some(_) => style.add_modifier(Modifier::DIM),
+            None => style,
+        };
+
+        let style = if !i.completed && config.required.contains(&i.text) {
+            style.add_modifier(Modifier::BOLD)
+        } else {
+            style
+        };
+
+        let style = match drag {
+            Some((from, to)) if to == index && from != to => {
+                style.add_modifier(Modifier::UNDERLINED)
+            }
+            _ => style,
+        };
+
+        let marker = if marked.contains(&index) { "* " } else { "" };
+
+        let glyph = config.glyph(i);
+
+        let mut suffix = match age {
+            Some(age) => format!(" ({}d)", age),
+            None => String::new(),
+        };
+        if let Some((_, _, started)) = timer
+            .as_ref()
+            .filter(|(path, text, _)| *path == *day_path && *text == i.text)
+        {
+            let seconds = started.elapsed().as_secs();
+            suffix.push_str(&format!(" (⏱ {}:{:02})", seconds / 60, seconds % 60));
+        }
+        if let Some(times) = i.carried.filter(|_| config.track_carried) {
+            suffix.push_str(&format!(" ↷{}", times));
+        }
+        let hidden = subtree_end(items, index) - index - 1;
+        if i.folded && hidden > 0 {
+            suffix.push_str(&format!(" (+{})", hidden));
+        }
+
+        let number = match config.line_numbers {
+            true => format!("{:>1$} ", index + 1, items.len().to_string().len()),
+            false => String::new(),
+        };
+        let mut spans = vec![Span::raw(format!(
+            "{}{}{}{} ",
+            number,
+            marker,
+            "  ".repeat(i.indent),
+            glyph
+        ))];
+        let body = i.body();
+        if let Some(query) = search.filter(|q| item_matches(i, q)) {
+            spans.extend(highlight_matches(&body, query));
+        } else if config.render_markdown {
+            spans.extend(markdown::spans(&body));
+        } else {
+            spans.push(Span::raw(body));
+        }
+        if !config.tag_colors.is_empty() {
+            spans = color_tags(config, spans);
+        }
+        spans.push(Span::raw(suffix));
+
+        ListItem::new(Line::from(spans)).style(style)
+    };
+
+    let unmet = match unmet_required(config, items).len() {
+        0 => String::new(),
+        n => format!(" ({} required left)", n),
+    };
+    // Lists without a date are never finished, so there is nothing to celebrate
+    let celebrate = !config.completion_messages.is_empty() && !is_undated(config, day_path);
+    let heading = match is_undated(config, day_path) {
+        true => day_name.to_string(),
+        false => title_text(config, day_name, day_offset, items),
+    };
+    let unmet = match sort_view {
+        Some(view) => format!("{} (view sorted by {})", unmet, view.name()),
+        None => unmet,
+    };
+    let title = if single {
+        format!("{}{}", heading, unmet)
+    } else {
+        format!(
+            "< {} | {}{}{} | {} >",
+            adjacent.0,
+            heading,
+            if rest_day { " (rest day)" } else { "" },
+            unmet,
+            adjacent.1
+        )
+    };
+    let mut block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(if over_wip_limit(config, items) {
+            Style::default().fg(Color::Red)
+        } else if celebrate && completion_percent(config, items) == Some(100) {
+            Style::default().fg(Color::Green)
+        } else {
+            Style::default()
+        });
+    if let Some(note) = note {
+        block = block
+            .title(block::Title::from(format!(" {} ", note)).position(block::Position::Bottom));
+    }
+
+    // Without styles, the selection needs a marker of its own
+    let highlight_symbol = match config.selection_symbol.as_str() {
+        "" if config.plain => "> ",
+        symbol => symbol,
+    };
+    let highlight_style = if config.high_contrast {
+        Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED)
+    } else {
+        Style::default()
+            .bg(Color::DarkGray)
+            .add_modifier(Modifier::BOLD)
+    };
+
+    let (msg, style) = match input_mode {
+        InputMode::Normal => (
+            vec![
+                Span::raw("Press "),
+                Span::styled("q", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to exit, "),
+                Span::styled("t", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to go to today, "),
+                Span::styled("T", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to show what is due today, "),
+                Span::styled("I", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to open the inbox, "),
+                Span::styled("M", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to open the someday list, "),
+                Span::styled("h", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to go yesterday, "),
+                Span::styled("l", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to go tomorrow, "),
+                Span::styled("H/L", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to jump back/forward, "),
+                Span::styled("k", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to move up, "),
+                Span::styled("j", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to move down, "),
+                Span::styled("n/N", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to go to the next/previous pending task, "),
+                Span::styled("r", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to reload, "),
+                Span::styled("e", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to edit in $EDITOR, "),
+                Span::styled("f/F", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to sort the view/apply the sort, "),
+                Span::styled("x", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to toggle, "),
+                Span::styled("~", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to cancel, "),
+                Span::styled("Space", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to mark, "),
+                Span::styled("C", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to cycle color, "),
+                Span::styled("O", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to open link, "),
+                Span::styled("c", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to copy the day, "),
+                Span::styled("b", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to toggle rest day, "),
+                Span::styled("a", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to add new todo, "),
+                Span::styled("o/i", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to add below/above, "),
+                Span::styled("A", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to add subtasks, "),
+                Span::styled("z/Z", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to fold/unfold, "),
+                Span::styled("p", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to show details, "),
+                Span::styled("d", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to remove."),
+            ],
+            if config.high_contrast {
+                Style::default().fg(Color::White)
+            } else {
+                Style::default().add_modifier(Modifier::RAPID_BLINK)
+            },
+        ),
+        InputMode::Review => (
+            vec![
+                Span::raw("Press "),
+                Span::styled("x", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to complete, "),
+                Span::styled("d", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to remove, "),
+                Span::styled("m", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to move to the next day, "),
+                Span::styled("Space", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to keep, "),
+                Span::styled("Esc", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to stop reviewing."),
+            ],
+            Style::default(),
+        ),
+        InputMode::Goto => (
+            vec![
+                Span::raw("Press "),
+                Span::styled("Esc", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to cancel, "),
+                Span::styled("Enter", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to jump to the task with that number."),
+            ],
+            Style::default(),
+        ),
+        InputMode::Search => (
+            vec![
+                Span::raw("Press "),
+                Span::styled("Esc", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to cancel, "),
+                Span::styled("Enter", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to search, then "),
+                Span::styled("n/N", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to jump between the matches."),
+            ],
+            Style::default(),
+        ),
+        _ => (
+            vec![
+                Span::raw("Press "),
+                Span::styled("Esc", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to stop editing, "),
+                Span::styled("Enter", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to write the todo."),
+            ],
+            Style::default(),
+        ),
+    };
+
+    let mut text = Text::from(Line::from(msg));
+    text.patch_style(style);
+
+    let help_message = Paragraph::new(text).wrap(Wrap { trim: true });
+
+    // Keep a third of the width for the goals of the month, if any
+    let mut list_area = chunks[0];
+    if let Some((name, goals)) = goals.filter(|_| size.width >= 60) {
+        let areas = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Ratio(1, 3)].as_ref())
+            .split(chunks[0]);
+        list_area = areas[0];
+
+        let goals = goals
+            .iter()
+            .map(|i| {
+                let glyph = config.glyph(i);
+
+                ListItem::new(format!("{}{} {}", "  ".repeat(i.indent), glyph, i.body()))
+            })
+            .collect::<Vec<_>>();
+        let goals = List::new(goals).block(
+            Block::default()
+                .title(format!("Goals {}", name))
+                .borders(Borders::ALL),
+        );
+        f.render_widget(goals, areas[1]);
+    }
+
+    // The detail pane takes the bottom of the list, as tall as its fields
+    if show_details {
+        let lines = match items_state.selected() {
+            Some(i) => item_details(&items[i], offset_day(config, day_offset))
+                .into_iter()
+                .map(|(label, value)| {
+                    Line::from(vec![
+                        Span::styled(
+                            format!("{}: ", label),
+                            Style::default().add_modifier(Modifier::BOLD),
+                        ),
+                        Span::raw(value),
+                    ])
+                })
+                .collect(),
+            None => vec![Line::from("No task selected")],
+        };
+        let height = u16::try_from(lines.len() + 2).unwrap_or(u16::MAX);
+
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(3), Constraint::Length(height)].as_ref())
+            .split(list_area);
+        list_area = areas[0];
+
+        let details = Paragraph::new(lines)
+            .wrap(Wrap { trim: true })
+            .block(Block::default().title("Details").borders(Borders::ALL));
+        f.render_widget(details, areas[1]);
+    }
+
+    // Flow the items into columns when they do not fit in one
+    let visible = shown_items(items, sort_view);
+    let mut rows_list = list_rows(items, &visible, sort_view);
+    let elsewhere = due_today
+        .filter(|_| day_offset == 0 && !single && !is_undated(config, day_path))
+        .unwrap_or_default();
+    rows_list.extend((0..elsewhere.len()).map(Row::Elsewhere));
+    let selected = items_state
+        .selected()
+        .and_then(|s| rows_list.iter().position(|&row| row == Row::Item(s)));
+    let window = |skip: usize, take: usize| {
+        return rows_list
+            .iter()
+            .skip(skip)
+            .take(take)
+            .map(|&row| match row {
+                Row::Item(i) => list_item((i, &items[i])),
+                Row::Heading(heading) => ListItem::new(Span::styled(
+                    heading.trim_start_matches('#').trim(),
+                    Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                )),
+                // Shown apart from the tasks of the day, since they cannot be changed here
+                Row::Elsewhere(e) => {
+                    let (from, item) = &elsewhere[e];
+                    ListItem::new(format!(
+                        "{} {} (from {})",
+                        config.glyph(item),
+                        item.body(),
+                        from
+                    ))
+                    .style(
+                        Style::default()
+                            .fg(Color::DarkGray)
+                            .add_modifier(Modifier::ITALIC),
+                    )
+                }
+            })
+            .collect::<Vec<_>>();
+    };
+
+    let inner = block.inner(list_area);
+    let rows = usize::from(inner.height).max(1);
+    let columns = match config.columns {
+        0 => usize::from(inner.width / 40),
+        n => n,
+    }
+    .min(rows_list.len().div_ceil(rows))
+    .max(1);
+
+    let mut list_offset = view.list_offset;
+    let (column_rows, list_inner);
+    if columns == 1 {
+        column_rows = None;
+        list_inner = Some(inner);
+
+        // Scroll just enough to keep the selection in view
+        if let Some(s) = selected {
+            list_offset = list_offset.clamp(s.saturating_sub(rows - 1), s);
+        }
+        list_offset = list_offset.min(rows_list.len().saturating_sub(rows));
+
+        let mut state = ListState::default();
+        state.select(selected.map(|s| s - list_offset));
+
+        let list = List::new(window(list_offset, rows))
+            .block(block)
+            .highlight_style(highlight_style)
+            .highlight_symbol(highlight_symbol);
+        f.render_stateful_widget(list, list_area, &mut state);
+    } else {
+        column_rows = Some(rows);
+        list_inner = None;
+
+        // Show the page of columns that holds the selection
+        let page = rows * columns;
+        let start = selected.map_or(0, |s| s / page * page);
+
+        let areas = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Ratio(1, columns as u32); columns])
+            .split(inner);
+
+        f.render_widget(block, list_area);
+
+        for (c, area) in areas.iter().enumerate() {
+            let first = start + c * rows;
+            let mut state = ListState::default();
+            state.select(
+                selected
+                    .and_then(|s| s.checked_sub(first))
+                    .filter(|&s| s < rows),
+            );
+
+            let list = List::new(window(first, rows))
+                .highlight_style(highlight_style)
+                .highlight_symbol(highlight_symbol);
+            f.render_stateful_widget(list, *area, &mut state);
+        }
+    }
+    f.render_widget(help_message, chunks[1]);
+
+    match input_mode {
+        InputMode::Normal | InputMode::Review => {
+            if config.show_path {
+                let p = Paragraph::new(Span::styled(
+                    day_path.display().to_string(),
+                    Style::default().fg(Color::DarkGray),
+                ))
+                .alignment(Alignment::Right);
+                f.render_widget(p, chunks[2]);
+            }
+
+            let carried = match items.iter().filter(|i| i.carried.is_some()).count() {
+                n if n > 0 && config.track_carried && !is_undated(config, day_path) => {
+                    format!("{} carried over", n)
+                }
+                _ => String::new(),
+            };
+            let shown = match completion_message(config, items) {
+                _ if !message.is_empty() => message,
+                Some(celebration) if celebrate => celebration,
+                _ => carried.as_str(),
+            };
+            let p = Paragraph::new(Span::styled(
+                shown,
+                Style::default().add_modifier(Modifier::ITALIC),
+            ));
+            f.render_widget(p, chunks[2]);
+        }
+        _ => {
+            let shown = match input_mode {
+                InputMode::Search => format!("/{}", prompt_text),
+                InputMode::Goto => format!(":{}", prompt_text),
+                _ => input_text.to_string(),
+            };
+            let p = Paragraph::new(Span::raw(shown.as_str()));
+            f.render_widget(p, chunks[2]);
+
+            let area = chunks[2];
+            if area.width > 0 && area.height > 0 {
+                let offset = u16::try_from(shown.len()).unwrap_or(u16::MAX);
+                let x = area.x.saturating_add(offset).min(area.right() - 1);
+                f.set_cursor(x, area.y);
+            }
+        }
+    }
+
+    if config.plain {
+        f.render_widget(Unstyled, size);
+    }
+
+    return ListLayout {
+        offset: list_offset,
+        column_rows,
+        inner: list_inner,
+    };
+}
+
+fn tui(config: config::Config) -> Result<()> {
+    let mut input_text = String::default();
+    // Text typed after `/` or `:`, and the search `n`/`N` jump to once submitted
+    let mut prompt_text = String::default();
+    let mut search: Option<String> = None;
+    let mut input_mode = InputMode::default();
+    let single = config.mode == config::Mode::Single;
+    let mut day_offset = match config.resume_last_day && !single {
+        true => read_last_day(&config).unwrap_or(0),
+        false => 0,
+    };
+    let mut day_name = if single {
+        "Todo".to_string()
+    } else {
+        date(&config, day_offset, &config.date_format)
+    };
+    let mut day_path = day_file(&config, &day_name);
+    let (mut items, warning) = read_day(&config, &day_path, &config.habits)?;
+    let mut message = warning
+        .or(size_warning(&config, &items))
+        .unwrap_or_default();
+    let mut items_state = ListState::default();
+    let mut marked: HashSet<usize> = HashSet::new();
+    let mut adjacent = adjacent_markers(&config, day_offset);
+    let mut rest_day = is_rest_day(&day_path);
+    let mut note = note_preview(&day_path);
+    let mut quit_pending = false;
+    // `P` pressed once, waiting for the second press to pull the pending tasks
+    let mut pull_pending = false;
+    // Editing key pressed once on a past day, with `edit_past` set to confirm
+    let mut edit_pending: Option<KeyCode> = None;
+    let mut show_details = false;
+    // Day file and text of the task being timed, and when it started
+    let mut timer: Option<(PathBuf, String, std::time::Instant)> = None;
+    // Days left with the navigation keys, for Ctrl+O to go back to
+    let mut day_history: Vec<i64> = Vec::new();
+    let write_options = WriteOptions::new(&config);
+    let inbox_path = config.inbox_file();
+    let someday_path = config.someday_file();
+    let mut goals = read_goals(&config, day_offset)?;
+    // Order the list is shown in with `f`, over the manual order of the file
+    let mut sort_view: Option<SortView> = None;
+    // Tasks due today from the other days, with their day, while `T` shows them
+    let mut due_today: Option<Vec<(String, Item)>> = None;
+    // Where the list was drawn in the last frame
+    let mut list_layout = ListLayout::default();
+    // Item being dragged with the mouse and the index it would be dropped at
+    let mut drag: Option<(usize, usize)> = None;
+
+    enter_tui()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+
+    loop {
+        let view = View {
+            config: &config,
+            items: &items,
+            items_state: &items_state,
+            marked: &marked,
+            input_mode: &input_mode,
+            input_text: &input_text,
+            prompt_text: &prompt_text,
+            search: search.as_deref(),
+            message: &message,
+            day_name: &day_name,
+            day_path: &day_path,
+            day_offset,
+            single,
+            adjacent: &adjacent,
+            rest_day,
+            note: note.as_deref(),
+            goals: goals.as_ref(),
+            show_details,
+            timer: timer.as_ref(),
+            sort_view,
+            due_today: due_today.as_deref(),
+            list_offset: list_layout.offset,
+            drag,
+        };
+        terminal.draw(|f| list_layout = draw(f, &view))?;
+
+        if event::poll(std::time::Duration::from_millis(50))? {
+            let event = event::read()?;
+
+            match event {
+                Event::FocusLost | Event::Resize(_, _) if config.save_on_focus_lost => {
+                    // Every change is saved as it is made, so a difference on disk
+                    // is an edit made elsewhere, which is never written over
+                    if is_dirty(&items, &day_path, &write_options) {
+                        message =
+                            "The file was changed elsewhere; press r to reload it".to_string();
+                    } else {
+                        write_items(&items, &day_path, &write_options)?;
+                    }
+                }
+                _ => {}
+            }
+
+            // Positions in the list skip the subtasks of folded items
+            let visible = shown_items(&items, sort_view);
+            // Typed `due:` phrases are relative to the day on screen
+            let due_base = match single || is_undated(&config, &day_path) {
+                true => today(&config),
+                false => offset_day(&config, day_offset),
+            };
+            let mut pasted = match &event {
+                Event::Paste(text) => Some(text.clone()),
+                _ => None,
+            };
+
+            if let (Event::Mouse(mouse), InputMode::Normal) = (&event, &input_mode) {
+                // Index of the item under the pointer, while the list is in one column
+                let rows_list = list_rows(&items, &visible, sort_view);
+                let row = list_layout
+                    .inner
+                    .filter(|area| mouse.row >= area.y && mouse.row < area.y + area.height)
+                    .and_then(|area| {
+                        rows_list.get(list_layout.offset + usize::from(mouse.row - area.y))
+                    })
+                    .and_then(|&row| match row {
+                        Row::Item(i) => Some(i),
+                        Row::Heading(_) | Row::Elsewhere(_) => None,
+                    });
+
+                match mouse.kind {
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        if let Some(i) = row {
+                            items_state.select(Some(i));
+                            // Dropping follows the manual order, which a sorted view hides;
+                            // a drag cannot be confirmed, so past days need `allow`
+                            if !can_edit_day(&config, day_offset, &day_path, false) {
+                                message = "Past days are only reordered with the mouse \
+                                           when edit_past is allow"
+                                    .to_string();
+                            } else if sort_view.is_none() {
+                                drag = Some((i, i));
+                            }
+                        }
+                    }
+                    MouseEventKind::Drag(MouseButton::Left) => {
+                        if let (Some((from, _)), Some(i)) = (drag, row) {
+                            drag = Some((from, i));
+                        }
+                    }
+                    MouseEventKind::Up(MouseButton::Left) => {
+                        if let Some((from, to)) = drag.take() {
+                            // Subtasks move along with their parent
+                            let end = subtree_end(&items, from);
+                            let at = match to {
+                                _ if to < from => Some(to),
+                                _ if to >= end => Some(subtree_end(&items, to) - (end - from)),
+                                _ => None,
+                            };
+
+                            if let Some(at) = at {
+                                reorder_items(&mut items, |items| {
+                                    let moved = items.drain(from..end).collect::<Vec<_>>();
+                                    items.splice(at..at, moved);
+                                });
+                                items_state.select(Some(at));
+                                marked.clear();
+
+                                write_items(&items, &day_path, &write_options)?;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            let position = items_state
+                .selected()
+                .and_then(|s| visible.iter().position(|&i| i == s));
+
+            if let Event::Key(key) = event {
+                if let InputMode::Normal = input_mode {
+                    message.clear();
+                }
+                let confirming_quit = std::mem::take(&mut quit_pending);
+                let confirming_pull = std::mem::take(&mut pull_pending);
+                let confirmed_edit = edit_pending.take() == Some(key.code);
+
+                match input_mode {
+                    InputMode::Normal => match key.code {
+                        KeyCode::Char('q')
+                            if config.confirm_quit
+                                && !confirming_quit
+                                && is_dirty(&items, &day_path, &write_options) =>
+                        {
+                            quit_pending = true;
+                            message = "The file was changed elsewhere; press q again to quit \
+                                       and keep that version, or r to reload it"
+                                .to_string();
+                        }
+                        KeyCode::Char('q') => {
+                            // Every change is already saved, so a difference can only come
+                            // from an edit made elsewhere, which is kept once confirmed
+                            let changed_elsewhere =
+                                config.confirm_quit && is_dirty(&items, &day_path, &write_options);
+                            if changed_elsewhere {
+                                items = peek_items(&day_path)?.unwrap_or_default();
+                            }
+                            if let Some(timer) = timer.take() {
+                                stop_timer(&mut items, &day_path, timer, &write_options)?;
+                            }
+                            if !changed_elsewhere {
+                                write_items(&items, &day_path, &write_options)?;
+                            }
+                            break;
+                        }
+                        // Every key that changes the day, apart from Ctrl+O going back
+                        KeyCode::Char('x')
+                        | KeyCode::Char('~')
+                        | KeyCode::Char('d')
+                        | KeyCode::Char('e')
+                        | KeyCode::Char('R')
+                        | KeyCode::Char('a')
+                        | KeyCode::Char('o')
+                        | KeyCode::Char('i')
+                        | KeyCode::Char('A')
+                        | KeyCode::Char('D')
+                        | KeyCode::Char('C')
+                        | KeyCode::Char('m')
+                        | KeyCode::Char('F')
+                        | KeyCode::Char('P')
+                        | KeyCode::Char('s')
+                        | KeyCode::Char('b')
+                            if (key.code != KeyCode::Char('o')
+                                || !key.modifiers.contains(KeyModifiers::CONTROL))
+                                && !can_edit_day(
+                                    &config,
+                                    day_offset,
+                                    &day_path,
+                                    // `P` waits for a second press of its own, which
+                                    // confirms the edit as well
+                                    confirmed_edit || key.code == KeyCode::Char('P'),
+                                ) =>
+                        {
+                            if config.edit_past == config::EditPast::Confirm {
+                                edit_pending = Some(key.code);
+                                message = "This day is in the past; press the key again to edit it"
+                                    .to_string();
+                            } else {
+                                message = "Past days are read-only (edit_past is deny)".to_string();
+                            }
+                        }
+                        KeyCode::Char('t')
+                        | KeyCode::Char('h')
+                        | KeyCode::Char('l')
+                        | KeyCode::Char('H')
+                        | KeyCode::Char('L')
+                        | KeyCode::Char('o')
+                            if !single
+                                && (key.code != KeyCode::Char('o')
+                                    || key.modifiers.contains(KeyModifiers::CONTROL)) =>
+                        {
+                            write_items(&items, &day_path, &write_options)?;
+
+                            let back = key.code == KeyCode::Char('o');
+                            let undated = is_undated(&config, &day_path);
+                            if !back && !undated {
+                                day_history.push(day_offset);
+                            }
+
+                            let jump = i64::from(config.jump_days);
+                            let no_previous = back && !undated && day_history.is_empty();
+                            day_offset = match key.code {
+                                // Leaving a list goes back to the day it was opened from
+                                KeyCode::Char('o') if undated => day_offset,
+                                KeyCode::Char('o') => day_history.pop().unwrap_or(day_offset),
+                                KeyCode::Char('h') => step_offset(&config, day_offset, -1),
+                                KeyCode::Char('l') => step_offset(&config, day_offset, 1),
+                                KeyCode::Char('H') => step_offset(&config, day_offset, -jump),
+                                KeyCode::Char('L') => step_offset(&config, day_offset, jump),
+                                _ => 0,
+                            };
+                            day_name = date(&config, day_offset, &config.date_format);
+                            day_path = day_file(&config, &day_name);
+                            log::debug!("Showing day {}", day_name);
+                            let warning;
+                            (items, warning) = read_day(&config, &day_path, &config.habits)?;
+                            message = warning
+                                .or(size_warning(&config, &items))
+                                .unwrap_or_default();
+                            items_state = ListState::default();
+                            marked.clear();
+                            adjacent = adjacent_markers(&config, day_offset);
+                            rest_day = is_rest_day(&day_path);
+                            note = note_preview(&day_path);
+                            goals = read_goals(&config, day_offset)?;
+
+                            if no_previous && message.is_empty() {
+                                message = "No previous day to go back to".to_string();
+                            }
+                        }
+                        KeyCode::Char('r') | KeyCode::Char('e') => {
+                            // Everything is saved as it changes, so only edits made
+                            // elsewhere are picked up here
+                            let habits: &[String] = if is_undated(&config, &day_path) {
+                                &[]
+                            } else {
+                                &config.habits
+                            };
+                            let selected = items_state.selected().map(|i| items[i].text.clone());
+
+                            (items, message) = match key.code {
+                                KeyCode::Char('e') => {
+                                    write_items(&items, &day_path, &write_options)?;
+                                    let editor =
+                                        std::env::var("EDITOR").unwrap_or("vi".to_string());
+                                    edit_day(
+                                        &config,
+                                        &day_path,
+                                        habits,
+                                        &editor,
+                                        leave_tui,
+                                        || {
+                                            enter_tui()?;
+                                            terminal.clear()?;
+                                            return Ok(());
+                                        },
+                                    )?
+                                }
+                                _ => reload_day(
+                                    &config,
+                                    &day_path,
+                                    habits,
+                                    "Reloaded from disk".to_string(),
+                                )?,
+                            };
+
+                            let index = selected.and_then(|text| {
+                                items
+                                    .iter()
+                                    .position(|i| i.text == text)
+                                    .or(items_state.selected())
+                            });
+                            items_state.select(index.filter(|&i| i < items.len()));
+                            marked.clear();
+                            adjacent = adjacent_markers(&config, day_offset);
+                            note = note_preview(&day_path);
+                        }
+                        KeyCode::Char('j') if !items.is_empty() => {
+                            let i = match position {
+                                Some(p) => (p + 1) % visible.len(),
+                                None => 0,
+                            };
+
+                            items_state.select(Some(visible[i]));
+                        }
+                        KeyCode::Left | KeyCode::Right if !items.is_empty() => {
+                            // Move to the same row of the neighbouring column
+                            if let (Some(rows), Some(p)) = (list_layout.column_rows, position) {
+                                let p = match key.code {
+                                    KeyCode::Left => p.saturating_sub(rows),
+                                    _ => (p + rows).min(visible.len() - 1),
+                                };
+
+                                items_state.select(Some(visible[p]));
+                            }
+                        }
+                        KeyCode::Char('k') if !items.is_empty() => {
+                            let i = match position {
+                                Some(p) => (p + visible.len() - 1) % visible.len(),
+                                None => visible.len() - 1,
+                            };
+
+                            items_state.select(Some(visible[i]));
+                        }
+                        KeyCode::Char('n') | KeyCode::Char('N') if !items.is_empty() => {
+                            let len = visible.len();
+                            let start = position;
+                            let next = (1..=len)
+                                .map(|step| match (key.code, start) {
+                                    (KeyCode::Char('n'), Some(i)) => (i + step) % len,
+                                    (KeyCode::Char('n'), None) => step - 1,
+                                    (_, Some(i)) => (i + len - step) % len,
+                                    (_, None) => len - step,
+                                })
+                                .find(|&p| match &search {
+                                    Some(query) => item_matches(&items[visible[p]], query),
+                                    None => items[visible[p]].is_open(),
+                                });
+
+                            match (next, &search) {
+                                (Some(p), _) => items_state.select(Some(visible[p])),
+                                (None, Some(query)) => {
+                                    message = format!("No task matches '{}'", query)
+                                }
+                                (None, None) => {
+                                    message = "All tasks are done, well done! 🎉".to_string()
+                                }
+                            }
+                        }
+                        KeyCode::Char(' ') => {
+                            if let Some(i) = items_state.selected() {
+                                if !marked.remove(&i) {
+                                    marked.insert(i);
+                                }
+                            }
+                        }
+                        KeyCode::Esc => {
+                            marked.clear();
+                            search = None;
+                        }
+                        KeyCode::Char('/') => {
+                            prompt_text.clear();
+                            input_mode = InputMode::Search;
+                        }
+                        KeyCode::Char(':') => {
+                            prompt_text.clear();
+                            input_mode = InputMode::Goto;
+                        }
+                        KeyCode::Char('x') => {
+                            let mut indices = if marked.is_empty() {
+                                items_state.selected().into_iter().collect()
+                            } else {
+                                marked.drain().collect::<Vec<_>>()
+                            };
+                            indices.sort_unstable();
+
+                            for i in indices {
+                                if let Some(note) = toggle_item(&config, day_offset, &mut items, i)?
+                                {
+                                    message = note;
+                                }
+                                if let Some(note) = stop_timer_on_done(
+                                    &config, &mut items, i, &day_path, &mut timer,
+                                )? {
+                                    message = note;
+                                }
+                            }
+                            adjacent = adjacent_markers(&config, day_offset);
+
+                            write_items(&items, &day_path, &write_options)?;
+                        }
+                        KeyCode::Char('p') => {
+                            show_details = !show_details;
+                        }
+                        KeyCode::Char('R') => match next_to_review(&items, 0) {
+                            Some(i) => {
+                                items_state.select(Some(i));
+                                marked.clear();
+                                input_mode = InputMode::Review;
+                                message = format!("{} task(s) to review", review_left(&items));
+                            }
+                            None => message = "Nothing left to review".to_string(),
+                        },
+                        KeyCode::Char('s') => {
+                            if let Some(i) = items_state.selected() {
+                                let timed = (day_path.clone(), items[i].text.clone());
+                                if timer
+                                    .as_ref()
+                                    .is_some_and(|(p, t, _)| (p, t) == (&timed.0, &timed.1))
+                                {
+                                    message = "This task is already being timed".to_string();
+                                } else {
+                                    // Only one timer runs at a time
+                                    if let Some(previous) = timer.take() {
+                                        stop_timer(
+                                            &mut items,
+                                            &day_path,
+                                            previous,
+                                            &write_options,
+                                        )?;
+                                    }
+                                    message = format!("Timing '{}'; press S to stop", timed.1);
+                                    timer = Some((timed.0, timed.1, std::time::Instant::now()));
+                                }
+                            }
+                        }
+                        KeyCode::Char('S') => match timer.take() {
+                            Some(timer) => {
+                                message = stop_timer(&mut items, &day_path, timer, &write_options)?
+                            }
+                            None => message = "No task is being timed".to_string(),
+                        },
+                        KeyCode::Char('z') => {
+                            if let Some(i) = items_state.selected() {
+                                if subtree_end(&items, i) > i + 1 {
+                                    items[i].folded = !items[i].folded;
+                                } else {
+                                    message = "This task has no subtasks to fold".to_string();
+                                }
+                            }
+                        }
+                        KeyCode::Char('T')
+                            if !single && day_offset == 0 && !is_undated(&config, &day_path) =>
+                        {
+                            due_today = match due_today {
+                                Some(_) => None,
+                                None => Some(due_elsewhere(&config, today(&config), None)?),
+                            };
+                            message = match due_today.as_ref().map(Vec::len) {
+                                Some(0) => "Nothing from other days is due today".to_string(),
+                                Some(n) => format!("{} task(s) from other days are due today", n),
+                                None => String::new(),
+                            };
+                        }
+                        KeyCode::Char('f') => {
+                            sort_view = SortView::next(sort_view);
+                            message = match sort_view {
+                                Some(view) => format!(
+                                    "Sorted by {} for now; press F to save this order",
+                                    view.name()
+                                ),
+                                None => "Back to the manual order".to_string(),
+                            };
+                        }
+                        KeyCode::Char('F') => match sort_view.take() {
+                            Some(view) => {
+                                let blocks = sorted_blocks(&items, view);
+                                // The selection stays on the same task at its new index
+                                let order = blocks.iter().cloned().flatten().collect::<Vec<_>>();
+                                items_state.select(
+                                    items_state
+                                        .selected()
+                                        .and_then(|s| order.iter().position(|&i| i == s)),
+                                );
+                                move_blocks(&mut items, blocks);
+                                marked.clear();
+                                message = format!("Saved the order by {} to the file", view.name());
+
+                                write_items(&items, &day_path, &write_options)?;
+                            }
+                            None => message = "Press f to choose a sort first".to_string(),
+                        },
+                        KeyCode::Char('Z') => {
+                            // Unfold everything if anything is folded, fold every parent otherwise
+                            let fold = !items.iter().any(|i| i.folded);
+                            for i in 0..items.len() {
+                                items[i].folded = fold && subtree_end(&items, i) > i + 1;
+                            }
+
+                            // Move a hidden selection up to its visible ancestor
+                            if let Some(s) = items_state.selected() {
+                                let visible = visible_items(&items);
+                                let ancestor = visible.iter().rev().find(|&&v| v <= s).copied();
+                                items_state.select(ancestor);
+                            }
+                        }
+                        KeyCode::Char('~') => {
+                            let indices = if marked.is_empty() {
+                                items_state.selected().into_iter().collect()
+                            } else {
+                                marked.drain().collect::<Vec<_>>()
+                            };
+
+                            for i in indices {
+                                items[i].cancel();
+                            }
+                            adjacent = adjacent_markers(&config, day_offset);
+
+                            write_items(&items, &day_path, &write_options)?;
+                        }
+                        KeyCode::Char('m') if !single => {
+                            match items_state.selected() {
+                                Some(i) if items[i].is_habit => {
+                                    message = "Habits already repeat every day".to_string();
+                                }
+                                Some(i) => {
+                                    // Subtasks go along with their parent
+                                    let end = subtree_end(&items, i);
+                                    let mut moved = remove_items(&mut items, i..end);
+                                    // A list sends it to the day it was opened from
+                                    let next = match is_undated(&config, &day_path) {
+                                        true => day_offset,
+                                        false => {
+                                            mark_carried(&config, &mut moved);
+                                            step_offset(&config, day_offset, 1)
+                                        }
+                                    };
+                                    let (name, warning) = move_to_day(&config, next, moved)?;
+                                    message = warning.unwrap_or(format!("Moved to {}", name));
+
+                                    if items.is_empty() {
+                                        items_state.select(None);
+                                    } else {
+                                        items_state.select(Some(i.min(items.len() - 1)));
+                                    }
+                                    marked.clear();
+                                    adjacent = adjacent_markers(&config, day_offset);
+
+                                    write_items(&items, &day_path, &write_options)?;
+                                }
+                                None => {}
+                            }
+                        }
+                        KeyCode::Char('C') => {
+                            if let Some(i) = items_state.selected() {
+                                items[i].cycle_color();
+                            }
+
+                            write_items(&items, &day_path, &write_options)?;
+                        }
+                        KeyCode::Char('I') if !single && day_path != inbox_path => {
+                            write_items(&items, &day_path, &write_options)?;
+
+                            day_name = "Inbox".to_string();
+                            day_path = inbox_path.clone();
+                            log::debug!("Showing the inbox");
+                            let warning;
+                            (items, warning) =
+                                read_items_with_warning(&day_path, &[], &write_options)?;
+                            message = warning
+                                .or(size_warning(&config, &items))
+                                .unwrap_or_default();
+                            items_state = ListState::default();
+                            marked.clear();
+                            rest_day = false;
+                            note = note_preview(&day_path);
+                            goals = None;
+                        }
+                        KeyCode::Char('P')
+                            if !single && day_offset != 0 && !is_undated(&config, &day_path) =>
+                        {
+                            let count = take_pending(&mut items.clone()).len();
+                            if count == 0 {
+                                message = "Nothing left to do on this day".to_string();
+                            } else if !confirming_pull {
+                                pull_pending = true;
+                                message =
+                                    format!("Press P again to move {} task(s) to today", count);
+                            } else {
+                                let mut pulled = take_pending(&mut items);
+                                if day_offset < 0 {
+                                    mark_carried(&config, &mut pulled);
+                                }
+                                let (_, warning) = move_to_day(&config, 0, pulled)?;
+                                write_items(&items, &day_path, &write_options)?;
+                                message =
+                                    warning.unwrap_or(format!("Moved {} task(s) to today", count));
+
+                                items_state.select(if items.is_empty() { None } else { Some(0) });
+                                marked.clear();
+                                adjacent = adjacent_markers(&config, day_offset);
+                            }
+                        }
+                        KeyCode::Char('M') if !single && day_path != someday_path => {
+                            write_items(&items, &day_path, &write_options)?;
+
+                            day_name = "Someday".to_string();
+                            day_path = someday_path.clone();
+                            log::debug!("Showing the someday list");
+                            let warning;
+                            (items, warning) =
+                                read_items_with_warning(&day_path, &[], &write_options)?;
+                            message = warning
+                                .or(size_warning(&config, &items))
+                                .unwrap_or_default();
+                            items_state = ListState::default();
+                            marked.clear();
+                            rest_day = false;
+                            note = note_preview(&day_path);
+                            goals = None;
+                        }
+                        KeyCode::Char('b') if !single && !is_undated(&config, &day_path) => {
+                            let path = rest_file(&day_path);
+
+                            if rest_day {
+                                fs::remove_file(path)?;
+                            } else {
+                                fs::write(path, "")?;
+                            }
+                            rest_day = !rest_day;
+                        }
+                        KeyCode::Char('c') => {
+                            message = copy_to_clipboard(&summary(&day_name, &items), &day_name)?;
+                        }
+                        KeyCode::Char('O') => {
+                            if let Some(i) = items_state.selected() {
+                                match items[i].url() {
+                                    Some(url) => {
+                                        if let Err(e) = open::that_detached(url) {
+                                            message = format!("Could not open {}: {}", url, e);
+                                        }
+                                    }
+                                    None => message = "No URL in the selected task".to_string(),
+                                }
+                            }
+                        }
+                        KeyCode::Char('a')
+                        | KeyCode::Char('o')
+                        | KeyCode::Char('i')
+                        | KeyCode::Char('A')
+                        | KeyCode::Char('D')
+                            if add_block_note(&config, &items, &day_path).is_some() =>
+                        {
+                            message =
+                                add_block_note(&config, &items, &day_path).unwrap_or_default();
+                        }
+                        KeyCode::Char('a') => {
+                            input_mode = InputMode::Insert;
+                        }
+                        KeyCode::Char('o') | KeyCode::Char('i') => {
+                            // Open a line below or above the selection, like in vim
+                            input_mode = match (key.code, items_state.selected()) {
+                                (KeyCode::Char('o'), Some(i)) => {
+                                    InputMode::InsertAt(subtree_end(&items, i), items[i].indent)
+                                }
+                                (_, Some(i)) => InputMode::InsertAt(i, items[i].indent),
+                                (_, None) => InputMode::Insert,
+                            };
+                        }
+                        KeyCode::Char('A') => {
+                            if let Some(i) = items_state.selected() {
+                                input_mode = InputMode::InsertChild(i);
+                            }
+                        }
+                        KeyCode::Char('D') => {
+                            if let Some(i) = items_state.selected() {
+                                let mut copy = items[i].clone();
+                                copy.completed = false;
+                                copy.cancelled = false;
+                                copy.done_at = None;
+                                copy.spent = None;
+                                if config.track_created {
+                                    copy.created = Some(today(&config));
+                                }
+
+                                // Land after the subtasks so they stay with the original
+                                let at = subtree_end(&items, i);
+                                insert_item(&mut items, at, copy);
+                                items_state.select(Some(at));
+                                marked.clear();
+
+                                write_items(&items, &day_path, &write_options)?;
+                            }
+                        }
+                        KeyCode::Char('d') if !marked.is_empty() => {
+                            let mut indices = marked.drain().collect::<Vec<_>>();
+                            indices.sort_unstable_by(|a, b| b.cmp(a));
+                            let removed = indices
+                                .into_iter()
+                                // Subtasks go with their parent, as `m` moves them
+                                .flat_map(|i| {
+                                    let end = subtree_end(&items, i);
+                                    return remove_items(&mut items, i..end);
+                                })
+                                .collect::<Vec<_>>();
+                            retain_history(&config, &day_path, &removed)?;
+
+                            if items.is_empty() {
+                                items_state.select(None);
+                            } else if let Some(i) = items_state.selected() {
+                                items_state.select(Some(i.min(items.len() - 1)));
+                            }
+
+                            write_items(&items, &day_path, &write_options)?;
+                        }
+                        KeyCode::Char('d') => {
+                            if let Some(i) = items_state.selected() {
+                                let end = subtree_end(&items, i);
+                                let removed = remove_items(&mut items, i..end);
+                                retain_history(&config, &day_path, &removed)?;
+
+                                if items.is_empty() {
+                                    items_state.select(None);
+                                } else {
+                                    items_state.select(Some((i + items.len() - 1) % items.len()));
+                                }
+                            }
+
+                            write_items(&items, &day_path, &write_options)?;
+                        }
+                        _ => {}
+                    },
+                    InputMode::Search => match key.code {
+                        KeyCode::Enter => {
+                            input_mode = InputMode::Normal;
+                            search =
+                                Some(std::mem::take(&mut prompt_text)).filter(|q| !q.is_empty());
+
+                            // Jump to the first match from the selection, like in vim
+                            if let Some(query) = &search {
+                                let start = items_state.selected().unwrap_or(0);
+                                let found = (start..items.len()).chain(0..start).find(|&i| {
+                                    visible.contains(&i) && item_matches(&items[i], query)
+                                });
+                                match found {
+                                    Some(i) => items_state.select(Some(i)),
+                                    None => message = format!("No task matches '{}'", query),
+                                }
+                            }
+                        }
+                        KeyCode::Char(c) => prompt_text.push(c),
+                        KeyCode::Backspace => {
+                            prompt_text.pop();
+                        }
+                        KeyCode::Esc => {
+                            input_mode = InputMode::Normal;
+                        }
+                        _ => {}
+                    },
+                    InputMode::Review => {
+                        let Some(i) = items_state.selected() else {
+                            input_mode = InputMode::Normal;
+                            continue;
+                        };
+                        let mut note = None;
+                        // Where to look for the next task once this one is handled
+                        let from = match key.code {
+                            KeyCode::Char('x') | KeyCode::Enter => {
+                                note = toggle_item(&config, day_offset, &mut items, i)?;
+                                note = stop_timer_on_done(
+                                    &config, &mut items, i, &day_path, &mut timer,
+                                )?
+                                .or(note);
+                                Some(i + 1)
+                            }
+                            KeyCode::Char('d') => {
+                                let end = subtree_end(&items, i);
+                                let removed = remove_items(&mut items, i..end);
+                                retain_history(&config, &day_path, &removed)?;
+                                Some(i)
+                            }
+                            KeyCode::Char('m') if !single => {
+                                let end = subtree_end(&items, i);
+                                let mut moved = remove_items(&mut items, i..end);
+                                let next = match is_undated(&config, &day_path) {
+                                    true => day_offset,
+                                    false => {
+                                        mark_carried(&config, &mut moved);
+                                        step_offset(&config, day_offset, 1)
+                                    }
+                                };
+                                (_, note) = move_to_day(&config, next, moved)?;
+                                Some(i)
+                            }
+                            KeyCode::Char(' ') => Some(i + 1),
+                            KeyCode::Esc => {
+                                input_mode = InputMode::Normal;
+                                message.clear();
+                                None
+                            }
+                            _ => None,
+                        };
+
+                        if let Some(from) = from {
+                            write_items(&items, &day_path, &write_options)?;
+                            adjacent = adjacent_markers(&config, day_offset);
+
+                            match next_to_review(&items, from) {
+                                Some(next) => {
+                                    items_state.select(Some(next));
+                                    message = note.unwrap_or_else(|| {
+                                        format!("{} task(s) to review", review_left(&items))
+                                    });
+                                }
+                                None => {
+                                    input_mode = InputMode::Normal;
+                                    items_state.select(if items.is_empty() {
+                                        None
+                                    } else {
+                                        Some(i.min(items.len() - 1))
+                                    });
+                                    message = "Review done".to_string();
+                                }
+                            }
+                        }
+                    }
+                    InputMode::Goto => match key.code {
+                        KeyCode::Enter => {
+                            input_mode = InputMode::Normal;
+                            let wanted = std::mem::take(&mut prompt_text);
+
+                            match wanted.trim().parse::<usize>() {
+                                Ok(n) if n >= 1 && n <= items.len() => {
+                                    if visible.contains(&(n - 1)) {
+                                        items_state.select(Some(n - 1));
+                                    } else {
+                                        message = format!("Task {} is folded away", n);
+                                    }
+                                }
+                                _ => {
+                                    message = format!(
+                                        "There is no task '{}'; the list has {}",
+                                        wanted,
+                                        items.len()
+                                    );
+                                }
+                            }
+                        }
+                        KeyCode::Char(c) if c.is_ascii_digit() => prompt_text.push(c),
+                        KeyCode::Backspace => {
+                            prompt_text.pop();
+                        }
+                        KeyCode::Esc => {
+                            input_mode = InputMode::Normal;
+                        }
+                        _ => {}
+                    },
+                    _ => match key.code {
+                        KeyCode::Enter => {
+                            // Checked on every task, as `stay_in_insert` adds several
+                            if let Some(note) = add_block_note(&config, &items, &day_path) {
+                                message = note;
+                                input_mode = InputMode::Normal;
+                            } else {
+                                submit_input(
+                                    &config,
+                                    &mut items,
+                                    &mut items_state,
+                                    &mut marked,
+                                    &mut input_mode,
+                                    typed_task(&config, &std::mem::take(&mut input_text), due_base),
+                                    config.stay_in_insert,
+                                );
+
+                                write_items(&items, &day_path, &write_options)?;
+                            }
+                        }
+                        KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            match arboard::Clipboard::new().and_then(|mut c| c.get_text()) {
+                                Ok(text) => pasted = Some(text),
+                                Err(_) => message = "Nothing to paste".to_string(),
+                            }
+                        }
+                        KeyCode::Char(c) => input_text.push(c),
+                        KeyCode::Backspace => {
+                            input_text.pop();
+                        }
+                        KeyCode::Esc => {
+                            input_mode = InputMode::Normal;
+                        }
+                        _ => {}
+                    },
+                }
+            }
+
+            if let (Some(text), InputMode::Search | InputMode::Goto) = (&pasted, &input_mode) {
+                prompt_text.push_str(&text.lines().collect::<Vec<_>>().join(" "));
+                pasted = None;
+            }
+
+            let typing = !matches!(input_mode, InputMode::Normal | InputMode::Review);
+            if let Some(text) = pasted.filter(|_| typing) {
+                let lines = text
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .collect::<Vec<_>>();
+
+                if config.paste_split_lines && lines.len() > 1 {
+                    // The first line completes what was already typed
+                    for line in lines {
+                        if let Some(note) = add_block_note(&config, &items, &day_path) {
+                            message = note;
+                            input_text.clear();
+                            break;
+                        }
+
+                        let task = format!("{}{}", std::mem::take(&mut input_text), line);
+                        let task = typed_task(&config, &task, due_base);
+                        submit_input(
+                            &config,
+                            &mut items,
+                            &mut items_state,
+                            &mut marked,
+                            &mut input_mode,
+                            task,
+                            true,
+                        );
+                    }
+                    if !config.stay_in_insert {
+                        input_mode = InputMode::Normal;
+                    }
+
+                    write_items(&items, &day_path, &write_options)?;
+                } else {
+                    input_text.push_str(&lines.join(" "));
+                }
+            }
+        }
+    }
+
+    // From the inbox or someday list, this is the day they were opened from
+    if config.resume_last_day && !single {
+        let day = offset_day(&config, day_offset)
+            .format("%Y-%m-%d")
+            .to_string();
+        fs::write(last_day_file(&config), day)?;
+    }
+
+    leave_tui()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::{backend::TestBackend, buffer::Buffer};
+    use std::cell::Cell;
+
+    /// Draw a day with two tasks, the first one selected, at `width` by `height`
+    fn draw_day(width: u16, height: u16) -> Result<Buffer> {
+        let config = config::Config::default();
+        let items: Vec<Item> = vec!["- [ ] Read".parse()?, "- [x] Gym".parse()?];
+        let mut items_state = ListState::default();
+        items_state.select(Some(0));
+        let marked = HashSet::new();
+        let adjacent = ("-".to_string(), "-".to_string());
+
+        let view = View {
+            config: &config,
+            items: &items,
+            items_state: &items_state,
+            marked: &marked,
+            input_mode: &InputMode::Normal,
+            input_text: "",
+            prompt_text: "",
+            search: None,
+            message: "",
+            day_name: "2026-10-14",
+            day_path: Path::new("2026-10-14.md"),
+            day_offset: 0,
+            single: false,
+            adjacent: &adjacent,
+            rest_day: false,
+            note: None,
+            goals: None,
+            show_details: false,
+            timer: None,
+            sort_view: None,
+            due_today: None,
+            list_offset: 0,
+            drag: None,
+        };
+
+        let mut terminal = Terminal::new(TestBackend::new(width, height))?;
+        terminal.draw(|f| {
+            draw(f, &view);
+        })?;
+
+        return Ok(terminal.backend().buffer().clone());
+    }
+
+    /// The text of each row of the buffer
+    fn rows(buffer: &Buffer) -> Vec<String> {
+        let area = buffer.area;
+        return (area.top()..area.bottom())
+            .map(|y| {
+                return (area.left()..area.right())
+                    .map(|x| buffer.get(x, y).symbol.as_str())
+                    .collect();
+            })
+            .collect();
+    }
+
+    #[test]
+    fn draw_keeps_the_list_on_a_small_terminal() -> Result<()> {
+        // Too small for the margin and the help line, but the tasks stay
+        assert_eq!(
+            rows(&draw_day(20, 5)?),
+            [
+                "┌< - | 2026-10-14 |┐",
+                "│☐ Read            │",
+                "│☑ Gym             │",
+                "└──────────────────┘",
+                "                    ",
+            ]
+        );
+
+        return Ok(());
+    }
+
+    #[test]
+    fn draw_fits_a_single_cell() -> Result<()> {
+        assert_eq!(rows(&draw_day(1, 1)?), ["┌"]);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn typed_task_drops_the_marker_of_the_template_and_resolves_its_due() {
+        let mut config = config::Config::default();
+        let day = NaiveDate::from_ymd_opt(2026, 10, 14).unwrap();
+
+        config.new_task_template = Some("- {} @inbox".to_string());
+        assert_eq!(typed_task(&config, "foo", day), "foo @inbox");
+
+        config.new_task_template = Some("- [ ] {} due:tomorrow".to_string());
+        assert_eq!(typed_task(&config, "foo", day), "foo due:2026-10-15");
+
+        config.new_task_template = Some("+work {}".to_string());
+        assert_eq!(typed_task(&config, "foo", day), "+work foo");
+    }
+
+    #[test]
+    fn blank_lines_around_headings_are_written_back_in_place() {
+        let config = config::Config {
+            keep_blank_lines: true,
+            keep_trailing_whitespace: true,
+            ..config::Config::default()
+        };
+        let options = WriteOptions::new(&config);
+
+        for data in [
+            "- [ ] a\n\n## Later\n\n- [ ] b\n",
+            "## Morning\n\n- [ ] a\n",
+            "\n- [ ] a\n",
+        ] {
+            let items = parse_lines(Path::new("day.md"), data);
+            assert_eq!(format_items(&items, &options), data);
+        }
+    }
+
+    #[test]
+    fn resolve_due_leaves_amounts_out_of_range_as_typed() {
+        let day = NaiveDate::from_ymd_opt(2026, 10, 14).unwrap();
+
+        assert_eq!(
+            resolve_due_phrase("+2w", day),
+            NaiveDate::from_ymd_opt(2026, 10, 28)
+        );
+        assert_eq!(resolve_due_phrase("+3000000000000000000w", day), None);
+        assert_eq!(resolve_due_phrase("+3000000000000000000d", day), None);
+    }
+
+    #[test]
+    fn edit_day_reloads_after_a_failing_editor() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("todo-tui-edit-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("2026-10-14.md");
+        std::fs::write(&path, "- [ ] Read\n")?;
+
+        let config = config::Config::default();
+        let editor = "sh -c 'printf \"%s\\n\" \"- [ ] Added\" >> \"$0\"; exit 3'";
+        let (left, entered) = (Cell::new(false), Cell::new(false));
+
+        let (items, message) = edit_day(
+            &config,
+            &path,
+            &[],
+            editor,
+            || {
+                left.set(true);
+                return Ok(());
+            },
+            || {
+                entered.set(true);
+                return Ok(());
+            },
+        )?;
+        std::fs::remove_dir_all(&dir)?;
+
+        assert!(left.get() && entered.get());
+        assert_eq!(
+            message,
+            format!("{} exited with status 3; reloaded the file", editor)
+        );
+        assert_eq!(
+            items.iter().map(|i| i.text.as_str()).collect::<Vec<_>>(),
+            ["Read", "Added"]
+        );
+
+        return Ok(());
+    }
+
+    #[test]
+    fn edit_day_restores_the_terminal_when_the_editor_cannot_start() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("todo-tui-leave-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("2026-10-14.md");
+        std::fs::write(&path, "- [ ] Read\n")?;
+
+        let entered = Cell::new(false);
+        let (items, message) = edit_day(
+            &config::Config::default(),
+            &path,
+            &[],
+            "false",
+            || bail!("no terminal"),
+            || {
+                entered.set(true);
+                return Ok(());
+            },
+        )?;
+        std::fs::remove_dir_all(&dir)?;
+
+        assert!(entered.get());
+        assert_eq!(
+            message,
+            "Could not run false: no terminal; reloaded the file"
+        );
+        assert_eq!(items.len(), 1);
+
+        return Ok(());
+    }
 }
@@ -0,0 +1,54 @@
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::Span,
+};
+
+/// Inline markers that are rendered, longest first so `**` wins over `*`
+const MARKERS: [&str; 3] = ["**", "*", "`"];
+
+fn marker_style(marker: &str) -> Style {
+    return match marker {
+        "**" => Style::default().add_modifier(Modifier::BOLD),
+        "*" => Style::default().add_modifier(Modifier::ITALIC),
+        _ => Style::default().fg(Color::Yellow),
+    };
+}
+
+/// Split text into spans, styling `**bold**`, `*italic*` and `` `code` `` runs
+///
+/// Only these three markers are supported and they do not nest. A marker without
+/// a closing counterpart, or one that wraps whitespace, is kept as plain text.
+pub fn spans(text: &str) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut plain = String::new();
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        if let Some(marker) = MARKERS.iter().find(|m| rest.starts_with(**m)) {
+            let after = &rest[marker.len()..];
+
+            if let Some(end) = after.find(marker) {
+                let inner = &after[..end];
+
+                if !inner.is_empty() && inner.trim() == inner {
+                    if !plain.is_empty() {
+                        spans.push(Span::raw(std::mem::take(&mut plain)));
+                    }
+                    spans.push(Span::styled(inner.to_string(), marker_style(marker)));
+
+                    rest = &after[end + marker.len()..];
+                    continue;
+                }
+            }
+        }
+
+        plain.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    if !plain.is_empty() {
+        spans.push(Span::raw(plain));
+    }
+
+    return spans;
+}